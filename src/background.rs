@@ -0,0 +1,256 @@
+use crate::{
+    bad_rand::rand_f64,
+    hittable::sphere::Sphere,
+    image::{Filter, HdrImage, Wrap},
+    sampling::Distribution2D,
+    vec3::Vec3,
+};
+use std::f64::consts::PI;
+
+/// The radiance rays see when they leave the scene without hitting anything.
+pub trait Background: Send + Sync {
+    /// `direction` is a unit vector pointing away from the scene.
+    fn value(&self, direction: &Vec3) -> Vec3;
+
+    /// Picks a direction towards the background, preferring bright parts of
+    /// it, and returns it along with its density over solid angle. `None` for
+    /// backgrounds that are only found by rays leaving the scene.
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        None
+    }
+
+    /// Density with which `sample` returns `direction`.
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        0.0
+    }
+}
+
+pub struct SolidBackground {
+    color: Vec3,
+}
+
+impl SolidBackground {
+    pub fn new(color: &Vec3) -> SolidBackground {
+        SolidBackground { color: *color }
+    }
+}
+
+impl Background for SolidBackground {
+    fn value(&self, _direction: &Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// Blends from `bottom` straight down to `top` straight up.
+pub struct GradientBackground {
+    bottom: Vec3,
+    top: Vec3,
+}
+
+impl GradientBackground {
+    pub fn new(bottom: &Vec3, top: &Vec3) -> GradientBackground {
+        GradientBackground {
+            bottom: *bottom,
+            top: *top,
+        }
+    }
+}
+
+impl Default for GradientBackground {
+    /// The white to blue sky the renderer has always used.
+    fn default() -> GradientBackground {
+        GradientBackground::new(&Vec3::new(1.0, 1.0, 1.0), &Vec3::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for GradientBackground {
+    fn value(&self, direction: &Vec3) -> Vec3 {
+        let w = 0.5 * (direction.y + 1.0);
+        self.bottom * (1.0 - w) + self.top * w
+    }
+}
+
+/// An equirectangular (latitude-longitude) image surrounding the scene, with
+/// +y up. It is laid out like the texture coordinates of a `Sphere`.
+///
+/// Directions are sampled in proportion to the luminance of the pixels, so
+/// that a small bright sun gets found by shadow rays instead of showing up as
+/// fireflies.
+pub struct EnvironmentMap {
+    image: HdrImage,
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// `rotation` turns the map around the y axis, in radians, and
+    /// `intensity` scales its radiance.
+    pub fn new(image: HdrImage, rotation: f64, intensity: f64) -> EnvironmentMap {
+        // an empty image, even one that is only empty along one axis, gets a
+        // single dark cell, so that there is something to sample
+        let (width, height) = match image.pixels() {
+            [] => (1, 1),
+            _ => (image.width(), image.height()),
+        };
+        let luminance: Vec<f64> = if image.pixels().is_empty() {
+            vec![0.0]
+        } else {
+            image
+                .pixels()
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    // rows near the poles cover less of the sphere
+                    let theta = PI * ((i / width) as f64 + 0.5) / height as f64;
+                    (0.2126 * p.x + 0.7152 * p.y + 0.0722 * p.z).max(0.0) * theta.sin()
+                })
+                .collect()
+        };
+        EnvironmentMap {
+            distribution: Distribution2D::new(&luminance, width, height),
+            image,
+            rotation,
+            intensity,
+        }
+    }
+
+    /// Image coordinates of `direction`, with the rotation applied.
+    pub fn uv(&self, direction: &Vec3) -> (f64, f64) {
+        let (u, v) = Sphere::get_uv(direction);
+        (u - self.rotation / (2.0 * PI), v)
+    }
+
+    /// Inverse of `uv`.
+    fn direction(&self, u: f64, v: f64) -> Vec3 {
+        let phi = 2.0 * PI * (u + self.rotation / (2.0 * PI));
+        let theta = PI * v;
+        Vec3::new(
+            -phi.cos() * theta.sin(),
+            -theta.cos(),
+            phi.sin() * theta.sin(),
+        )
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn value(&self, direction: &Vec3) -> Vec3 {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let (u, v) = self.uv(direction);
+        // stay between the centers of the first and last row, so that the
+        // filter doesn't blend the poles with each other
+        let half_row = 0.5 / self.image.height() as f64;
+        let v = v.clamp(half_row, 1.0 - half_row);
+        self.image.sample(u, v, Filter::Bilinear, Wrap::Repeat) * self.intensity
+    }
+
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        // the distribution has the top row first, like the image
+        let ((u, row), pdf) = self.distribution.sample(rand_f64(), rand_f64());
+        let v = 1.0 - row;
+        let sin_theta = (PI * v).sin();
+        if pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        // from the unit square to the sphere, which is 2 pi by pi large
+        Some((self.direction(u, v), pdf / (2.0 * PI * PI * sin_theta)))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u.rem_euclid(1.0), 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Background, EnvironmentMap, GradientBackground};
+    use crate::{bad_rand, image::HdrImage, vec3::Vec3};
+
+    #[test]
+    fn test_gradient() {
+        let sky = GradientBackground::default();
+        assert_eq!(sky.value(&Vec3::new(0.0, 1.0, 0.0)).x, 0.5);
+        assert_eq!(sky.value(&Vec3::new(0.0, -1.0, 0.0)).x, 1.0);
+    }
+
+    #[test]
+    fn test_environment_map() {
+        // a 4x2 map: the upper row is bright, the lower one dark, and the
+        // second column is red
+        let pixels = (0..8)
+            .map(|i| {
+                let level = if i < 4 { 1.0 } else { 0.1 };
+                let red = if i % 4 == 1 { 2.0 } else { 1.0 };
+                Vec3::new(level * red, level, level)
+            })
+            .collect();
+        let map = EnvironmentMap::new(HdrImage::new(2, 4, pixels), 0.0, 3.0);
+
+        let up = map.value(&Vec3::new(0.0, 1.0, 0.0));
+        assert!((up.y - 3.0).abs() < 1e-12);
+        let down = map.value(&Vec3::new(0.0, -1.0, 0.0));
+        assert!((down.y - 0.3).abs() < 1e-12);
+
+        // +z is a quarter turn into the map, and the second column is centered
+        // another eighth further
+        let (u, _) = map.uv(&Vec3::new(0.0, 0.0, 1.0));
+        assert!((u - 0.25).abs() < 1e-12);
+        let side = map.value(&Vec3::new(1.0, 0.0, 1.0).normal());
+        assert!((side.x - 2.0 * side.y).abs() < 1e-12);
+
+        let rotated = EnvironmentMap::new(
+            HdrImage::new(1, 1, vec![Vec3::new(1.0, 1.0, 1.0)]),
+            1.0,
+            1.0,
+        );
+        assert!(
+            (rotated.uv(&Vec3::new(0.0, 0.0, 1.0)).0 - (0.25 - 1.0 / std::f64::consts::TAU)).abs()
+                < 1e-12
+        );
+
+        for (height, width) in [(0, 0), (0, 5), (5, 0)] {
+            let empty = EnvironmentMap::new(HdrImage::new(height, width, Vec::new()), 0.0, 1.0);
+            assert_eq!(empty.value(&Vec3::new(0.0, 1.0, 0.0)).y, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_environment_sampling() {
+        bad_rand::seed(11);
+        // dim everywhere except for a single bright pixel
+        let mut pixels = vec![Vec3::new(0.1, 0.1, 0.1); 16 * 8];
+        pixels[2 * 16 + 5] = Vec3::new(1000.0, 1000.0, 1000.0);
+        let map = EnvironmentMap::new(HdrImage::new(8, 16, pixels), 0.7, 1.0);
+
+        let mut bright = 0;
+        for _ in 0..1000 {
+            let (direction, pdf) = map.sample().unwrap();
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            assert!((map.pdf(&direction) - pdf).abs() < 1e-9 * pdf);
+            let (u, v) = map.uv(&direction);
+            let (column, row) = (
+                (u.rem_euclid(1.0) * 16.0) as usize,
+                ((1.0 - v) * 8.0) as usize,
+            );
+            if (column, row) == (5, 2) {
+                bright += 1;
+            }
+        }
+        assert!(bright > 900);
+
+        // the density integrates to one over the sphere
+        let n = 200_000;
+        let mean: f64 = (0..n)
+            .map(|_| map.pdf(&Vec3::random_unit_vector()))
+            .sum::<f64>()
+            / n as f64;
+        assert!((mean * 4.0 * std::f64::consts::PI - 1.0).abs() < 0.05);
+    }
+}
@@ -1,14 +1,56 @@
 use quad_rand;
+use std::cell::Cell;
 
-// static mut SEED: AtomicUsize = AtomicUsize::new(123456789);
-// const A: usize = 1103515245;
-// const C: usize = 12345;
-// const M: usize = 2_usize.pow(31);
+// Every thread owns a small PCG generator so that render workers neither
+// contend on a shared state nor draw the same numbers. The renderer restarts
+// it with `seed_stream` for every row of the image, so the image only depends
+// on the seed and not on which thread renders which row. Anything else draws
+// from a generator seeded from quad_rand's global state the first time it is
+// used, which keeps it reproducible after `seed`.
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
 
+thread_local! {
+    static STATE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Seeds the global generator and restarts the calling thread's generator from it.
+pub fn seed(seed: u64) {
+    quad_rand::srand(seed);
+    STATE.with(|state| state.set(Some(seed_from_global())));
+}
+
+/// Restarts the calling thread's generator on one of many independent streams
+/// derived from `seed`, independent of the global generator.
+pub fn seed_stream(seed: u64, stream: u64) {
+    // splitmix64 finalizer to decorrelate neighbouring streams
+    let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    STATE.with(|state| state.set(Some(z ^ (z >> 31))));
+}
+
+fn seed_from_global() -> u64 {
+    ((quad_rand::rand() as u64) << 32) | quad_rand::rand() as u64
+}
+
+fn next_u32() -> u32 {
+    STATE.with(|state| {
+        let old_state = state.get().unwrap_or_else(seed_from_global);
+        state.set(Some(old_state.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT)));
+
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    })
+}
+
+/// Returns a uniformly distributed value in `[0, 1)`.
 pub fn rand_f64() -> f64 {
-    quad_rand::gen_range(0., 1.)
+    next_u32() as f64 / (u32::MAX as f64 + 1.0)
 }
 
 pub fn rand_usize(range: std::ops::Range<usize>) -> usize {
-    quad_rand::gen_range(range.start, range.end)
-}
\ No newline at end of file
+    let len = range.end - range.start;
+    range.start + ((rand_f64() * len as f64) as usize).min(len.saturating_sub(1))
+}
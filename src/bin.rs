@@ -1,55 +1,80 @@
 use raytracer::{
-    camera::Camera,
-    hittable:: Hittable,
-    vec3::Vec3,
-    create_random_scene,
-    render
+    bad_rand,
+    cli::{self, Command},
+    image::ImageFormat,
+    integrator::{Aov, IntegratorKind},
+    render, render_aov,
+    scene::Scene,
+    DEFAULT_SEED,
 };
-use std::{fs::File, io::Write};
-
-
-fn main() -> std::io::Result<()> {
-    quad_rand::srand(123456789);
-    
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-
-    const IMG_HEIGHT: usize = 1200;
-    const IMG_WIDTH: usize = (IMG_HEIGHT as f64 * ASPECT_RATIO) as usize;
-
-    const SAMPLES_PER_PIXEL: usize = 100;
-
-    const MAX_DEPTH: usize = 50;
-
-    let world: Vec<Box<dyn Hittable>> = create_random_scene();
-
-    let look_from = Vec3::new(13.0, 2.0, 3.0);
-    let look_at = Vec3::new(0.0, 0.0, 0.0);
-    let view_up = Vec3::new(0.0, 1.0, 0.0);
-    let vfov = 20.0;
-    let aperture = 0.1;
-    let focus_dist = 10.0;
-
-    let camera = Camera::new(
-        &look_from,
-        &look_at,
-        &view_up,
-        vfov,
-        ASPECT_RATIO,
-        aperture,
-        focus_dist,
-    );
-
-    let image = render(
-        IMG_HEIGHT,
-        IMG_WIDTH,
-        SAMPLES_PER_PIXEL,
-        MAX_DEPTH,
-        &camera,
-        &world,
-    );
-
-    let mut f = File::create("test.ppm")?;
-    f.write_all(image.to_ppm().as_bytes())?;
-    Ok(())
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+
+fn main() {
+    let options = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("error: {error}\nRun 'raytracerbin --help' for the list of options.");
+            process::exit(2);
+        }
+    };
+
+    // the seed also decides how the random scene is laid out
+    bad_rand::seed(options.seed.unwrap_or(DEFAULT_SEED));
+
+    let scene = match &options.scene {
+        Some(path) => Scene::load(path),
+        None => Ok(Scene::random()),
+    };
+    let mut scene = match scene {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("error: {error}");
+            process::exit(1);
+        }
+    };
+    options.apply(&mut scene);
+
+    let image = render(&scene);
+
+    // passes hold data rather than colors, so they are written without gamma
+    match scene.settings.integrator {
+        IntegratorKind::Aov(aov) => {
+            let (path, format) = aov_output(options.output.clone(), aov, options.format);
+            write_image(&path, &image.encode_linear(format));
+        }
+        _ => write_image(&options.output, &image.encode(options.format)),
+    }
+
+    for &aov in &scene.settings.aovs {
+        let image = render_aov(&scene, aov);
+        let path = cli::aov_path(&options.output, aov);
+        let (path, format) = aov_output(path, aov, options.format);
+        write_image(&path, &image.encode_linear(format));
+    }
 }
 
+/// Where to write `aov` and in which format. Passes that don't fit into PNG
+/// or PPM files are written as OpenEXR instead.
+fn aov_output(path: PathBuf, aov: Aov, format: ImageFormat) -> (PathBuf, ImageFormat) {
+    if aov.fits_unit_range() || format.is_hdr() {
+        (path, format)
+    } else {
+        (path.with_extension("exr"), ImageFormat::Exr)
+    }
+}
+
+fn write_image(path: &Path, data: &[u8]) {
+    if let Err(error) = fs::write(path, data) {
+        eprintln!("error: {}: {error}", path.display());
+        process::exit(1);
+    }
+}
@@ -1,4 +1,54 @@
-use crate::{ray::Ray, vec3::Vec3};
+use crate::{bad_rand::rand_f64, ray::Ray, vec3::Vec3};
+
+/// The parameters `Camera::new` takes, kept around so that they can be read
+/// from a scene file and overridden before the camera is built.
+#[derive(Clone)]
+pub struct CameraSettings {
+    pub look_from: Vec3,
+    pub look_at: Vec3,
+    pub view_up: Vec3,
+    pub vertical_field_of_view_deg: f64,
+    pub aspect_ratio: f64,
+    pub aperture: f64,
+    pub focus_dist: f64,
+    /// Times at which the shutter opens and closes. Objects that move in
+    /// between are blurred along their path. Scene files move objects from
+    /// time 0 to time 1, so `0` and `1` see all of their motion, `0.5` and
+    /// `1` the second half of it.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl CameraSettings {
+    pub fn build(&self) -> Camera {
+        Camera::new(
+            &self.look_from,
+            &self.look_at,
+            &self.view_up,
+            self.vertical_field_of_view_deg,
+            self.aspect_ratio,
+            self.aperture,
+            self.focus_dist,
+        )
+        .with_shutter(self.shutter_open, self.shutter_close)
+    }
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            look_from: Vec3::new(13.0, 2.0, 3.0),
+            look_at: Vec3::new(0.0, 0.0, 0.0),
+            view_up: Vec3::new(0.0, 1.0, 0.0),
+            vertical_field_of_view_deg: 20.0,
+            aspect_ratio: 16.0 / 9.0,
+            aperture: 0.1,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+}
 
 pub struct Camera {
     origin: Vec3,
@@ -8,6 +58,8 @@ pub struct Camera {
     v: Vec3,
     u: Vec3,
     lens_radius: f64,
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -41,6 +93,18 @@ impl Camera {
             u,
             v,
             lens_radius: aperture / 2.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    /// Fires rays at random times between `open` and `close` instead of all
+    /// at time 0.
+    pub fn with_shutter(self, open: f64, close: f64) -> Camera {
+        Camera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
         }
     }
 
@@ -50,7 +114,14 @@ impl Camera {
         let new_origin = self.origin + offset;
         let direction =
             self.lower_left_corner + self.horizontal * s + self.vertical * t - new_origin;
-        Ray::new(&new_origin, &direction)
+        // an instantaneous shutter leaves the random numbers of still images
+        // as they were
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + rand_f64() * (self.shutter_close - self.shutter_open)
+        } else {
+            self.shutter_open
+        };
+        Ray::with_time(&new_origin, &direction, time)
     }
 
     fn deg_to_rad(deg: f64) -> f64 {
@@ -0,0 +1,517 @@
+use crate::{
+    image::ImageFormat,
+    integrator::{Aov, IntegratorKind},
+    scene::Scene,
+    vec3::Vec3,
+};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+pub const USAGE: &str = "\
+Usage: raytracerbin [OPTIONS] [SCENE]
+
+Renders SCENE, a JSON scene description, or the built-in random scene.
+
+Options:
+      --scene <PATH>            Scene file to render (same as SCENE)
+  -o, --output <PATH>           Output image [default: test.ppm]
+      --format <FORMAT>         Output format, otherwise taken from the output extension
+                                [ppm, png, png16, hdr, exr, exr32]
+      --width <PIXELS>          Image width
+      --height <PIXELS>         Image height
+      --aspect-ratio <RATIO>    Width over height, e.g. 1.5 or 16:9
+      --spp <N>                 Samples per pixel
+      --max-depth <N>           Maximum number of bounces per path
+      --roulette-depth <N>      Bounces before paths may be ended at random
+      --integrator <NAME>       Rendering algorithm [default: path]
+                                [path, recursive_path, whitted, ambient_occlusion,
+                                normal, front_face, depth, albedo, uv, object_id]
+      --aov <NAMES>             Comma separated passes to write next to the output, e.g.
+                                normal,depth to get out.normal.png and out.depth.png
+      --seed <N>                Seed for the random number generator
+      --threads <N>             Number of render threads
+      --look-from <X,Y,Z>       Camera position
+      --look-at <X,Y,Z>         Point the camera looks at
+      --view-up <X,Y,Z>         Camera up direction
+      --vfov <DEGREES>          Vertical field of view
+      --aperture <SIZE>         Lens aperture, 0 for a pinhole camera
+      --focus-dist <DISTANCE>   Distance to the plane in focus
+      --shutter <OPEN,CLOSE>    Times the shutter is open between, for motion blur
+  -h, --help                    Print this help
+";
+
+// options that take a value, all of them except for --help
+const FLAGS: &[&str] = &[
+    "--scene",
+    "-o",
+    "--output",
+    "--format",
+    "--width",
+    "--height",
+    "--aspect-ratio",
+    "--spp",
+    "--max-depth",
+    "--roulette-depth",
+    "--integrator",
+    "--aov",
+    "--seed",
+    "--threads",
+    "--look-from",
+    "--look-at",
+    "--view-up",
+    "--vfov",
+    "--aperture",
+    "--focus-dist",
+    "--shutter",
+];
+
+pub enum Command {
+    Render(Box<Options>),
+    Help,
+}
+
+/// Everything that can be set from the command line. Unset values keep
+/// whatever the scene specifies.
+#[derive(Default)]
+pub struct Options {
+    pub scene: Option<PathBuf>,
+    pub output: PathBuf,
+    pub format: ImageFormat,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub aspect_ratio: Option<f64>,
+    pub samples_per_pixel: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub roulette_depth: Option<usize>,
+    pub integrator: Option<IntegratorKind>,
+    pub aovs: Option<Vec<Aov>>,
+    pub seed: Option<u64>,
+    pub threads: Option<usize>,
+    pub look_from: Option<Vec3>,
+    pub look_at: Option<Vec3>,
+    pub view_up: Option<Vec3>,
+    pub vfov: Option<f64>,
+    pub aperture: Option<f64>,
+    pub focus_dist: Option<f64>,
+    pub shutter: Option<(f64, f64)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CliError(pub String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Parses the arguments following the program name.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, CliError> {
+    let mut options = Options {
+        output: PathBuf::from("test.ppm"),
+        ..Options::default()
+    };
+    let mut format = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            if options.scene.is_some() {
+                return Err(CliError(format!("unexpected argument '{arg}'")));
+            }
+            options.scene = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        // accept both `--flag value` and `--flag=value`
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(Command::Help);
+        }
+        if !FLAGS.contains(&flag.as_str()) {
+            return Err(CliError(format!("unknown option '{flag}'")));
+        }
+        let value = match inline_value {
+            Some(value) => value,
+            None => args
+                .next()
+                .ok_or_else(|| CliError(format!("'{flag}' needs a value")))?,
+        };
+
+        match flag.as_str() {
+            "--scene" => {
+                if options.scene.is_some() {
+                    return Err(CliError("the scene was given more than once".to_string()));
+                }
+                options.scene = Some(PathBuf::from(value))
+            }
+            "-o" | "--output" => options.output = PathBuf::from(value),
+            "--format" => {
+                format = Some(
+                    ImageFormat::from_name(&value)
+                        .ok_or_else(|| CliError(format!("unsupported format '{value}'")))?,
+                )
+            }
+            "--width" => options.width = Some(parse_positive(&flag, &value)?),
+            "--height" => options.height = Some(parse_positive(&flag, &value)?),
+            "--aspect-ratio" => options.aspect_ratio = Some(parse_aspect_ratio(&value)?),
+            "--spp" => options.samples_per_pixel = Some(parse_positive(&flag, &value)?),
+            "--max-depth" => options.max_depth = Some(parse_positive(&flag, &value)?),
+            "--roulette-depth" => {
+                options.roulette_depth = Some(value.parse().map_err(|_| {
+                    CliError(format!(
+                        "'{flag}' expects a non-negative integer, got '{value}'"
+                    ))
+                })?)
+            }
+            "--integrator" => {
+                options.integrator = Some(IntegratorKind::from_name(&value).ok_or_else(|| {
+                    CliError(format!("unknown integrator '{value}'"))
+                })?)
+            }
+            "--aov" => {
+                options.aovs = Some(
+                    value
+                        .split(',')
+                        .map(|name| {
+                            Aov::from_name(name.trim())
+                                .ok_or_else(|| CliError(format!("unknown pass '{name}'")))
+                        })
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            "--seed" => {
+                options.seed = Some(value.parse().map_err(|_| {
+                    CliError(format!(
+                        "'{flag}' expects a non-negative integer, got '{value}'"
+                    ))
+                })?)
+            }
+            "--threads" => options.threads = Some(parse_positive(&flag, &value)?),
+            "--look-from" => options.look_from = Some(parse_vec3(&flag, &value)?),
+            "--look-at" => options.look_at = Some(parse_vec3(&flag, &value)?),
+            "--view-up" => options.view_up = Some(parse_vec3(&flag, &value)?),
+            "--vfov" => {
+                let vfov = parse_f64(&flag, &value)?;
+                if vfov <= 0.0 || vfov >= 180.0 {
+                    return Err(CliError(format!(
+                        "'{flag}' must be between 0 and 180 degrees"
+                    )));
+                }
+                options.vfov = Some(vfov);
+            }
+            "--aperture" => {
+                let aperture = parse_f64(&flag, &value)?;
+                if aperture < 0.0 {
+                    return Err(CliError(format!("'{flag}' can't be negative")));
+                }
+                options.aperture = Some(aperture);
+            }
+            "--focus-dist" => {
+                let focus_dist = parse_f64(&flag, &value)?;
+                if focus_dist <= 0.0 {
+                    return Err(CliError(format!("'{flag}' must be positive")));
+                }
+                options.focus_dist = Some(focus_dist);
+            }
+            "--shutter" => {
+                let times = value
+                    .split(',')
+                    .map(|time| parse_f64(&flag, time.trim()))
+                    .collect::<Result<Vec<f64>, CliError>>()?;
+                match times[..] {
+                    [open, close] if open <= close => options.shutter = Some((open, close)),
+                    [_, _] => {
+                        return Err(CliError(format!(
+                            "'{flag}' must open the shutter before closing it"
+                        )))
+                    }
+                    _ => {
+                        return Err(CliError(format!(
+                            "'{flag}' expects two comma separated times, got '{value}'"
+                        )))
+                    }
+                }
+            }
+            _ => unreachable!("every flag in FLAGS is handled"),
+        }
+    }
+
+    options.format = match format {
+        Some(format) => format,
+        None => ImageFormat::from_path(&options.output).ok_or_else(|| {
+            CliError(format!(
+                "can't tell the format of '{}' from its extension, use --format",
+                options.output.display()
+            ))
+        })?,
+    };
+
+    if let (Some(width), Some(height), Some(aspect_ratio)) =
+        (options.width, options.height, options.aspect_ratio)
+    {
+        // the width only has to be right up to rounding to whole pixels
+        if (aspect_ratio * height as f64 - width as f64).abs() >= 1.0 {
+            return Err(CliError(format!(
+                "the aspect ratio doesn't match the image size of {width} x {height}"
+            )));
+        }
+    }
+
+    Ok(Command::Render(Box::new(options)))
+}
+
+impl Options {
+    /// Applies the overrides to a loaded scene. A changed aspect ratio keeps
+    /// the image height unless the height is the one being set.
+    pub fn apply(&self, scene: &mut Scene) {
+        let camera = &mut scene.camera;
+        let settings = &mut scene.settings;
+
+        match (self.width, self.height, self.aspect_ratio) {
+            (Some(width), Some(height), aspect_ratio) => {
+                settings.image_width = width;
+                settings.image_height = height;
+                camera.aspect_ratio = aspect_ratio.unwrap_or(width as f64 / height as f64);
+            }
+            (Some(width), None, aspect_ratio) => {
+                camera.aspect_ratio = aspect_ratio.unwrap_or(camera.aspect_ratio);
+                settings.image_width = width;
+                settings.image_height = ((width as f64 / camera.aspect_ratio) as usize).max(1);
+            }
+            (None, height, aspect_ratio) => {
+                camera.aspect_ratio = aspect_ratio.unwrap_or(camera.aspect_ratio);
+                settings.image_height = height.unwrap_or(settings.image_height);
+                if height.is_some() || aspect_ratio.is_some() {
+                    settings.image_width =
+                        ((settings.image_height as f64 * camera.aspect_ratio) as usize).max(1);
+                }
+            }
+        }
+
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            settings.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(roulette_depth) = self.roulette_depth {
+            settings.roulette_depth = roulette_depth;
+        }
+        if let Some(integrator) = self.integrator {
+            settings.integrator = integrator;
+        }
+        if let Some(aovs) = &self.aovs {
+            settings.aovs = aovs.clone();
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(threads) = self.threads {
+            settings.threads = threads;
+        }
+
+        if let Some(look_from) = self.look_from {
+            camera.look_from = look_from;
+        }
+        if let Some(look_at) = self.look_at {
+            camera.look_at = look_at;
+        }
+        if let Some(view_up) = self.view_up {
+            camera.view_up = view_up;
+        }
+        if let Some(vfov) = self.vfov {
+            camera.vertical_field_of_view_deg = vfov;
+        }
+        if let Some(aperture) = self.aperture {
+            camera.aperture = aperture;
+        }
+        if let Some(focus_dist) = self.focus_dist {
+            camera.focus_dist = focus_dist;
+        }
+        if let Some((open, close)) = self.shutter {
+            camera.shutter_open = open;
+            camera.shutter_close = close;
+        }
+    }
+}
+
+/// Where the `aov` pass of an image written to `output` goes: next to it,
+/// with the name of the pass before the extension.
+pub fn aov_path(output: &Path, aov: Aov) -> PathBuf {
+    let mut name = output.file_stem().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(aov.name());
+    if let Some(extension) = output.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    output.with_file_name(name)
+}
+
+fn parse_f64(flag: &str, value: &str) -> Result<f64, CliError> {
+    match value.parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(CliError(format!(
+            "'{flag}' expects a number, got '{value}'"
+        ))),
+    }
+}
+
+fn parse_positive(flag: &str, value: &str) -> Result<usize, CliError> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(CliError(format!(
+            "'{flag}' expects a positive integer, got '{value}'"
+        ))),
+    }
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64, CliError> {
+    let ratio = match value.split_once(':') {
+        Some((width, height)) => {
+            parse_f64("--aspect-ratio", width)? / parse_f64("--aspect-ratio", height)?
+        }
+        None => parse_f64("--aspect-ratio", value)?,
+    };
+    if !(ratio.is_finite() && ratio > 0.0) {
+        return Err(CliError(format!(
+            "'--aspect-ratio' must be positive, got '{value}'"
+        )));
+    }
+    Ok(ratio)
+}
+
+fn parse_vec3(flag: &str, value: &str) -> Result<Vec3, CliError> {
+    let components = value
+        .split(',')
+        .map(|component| parse_f64(flag, component.trim()))
+        .collect::<Result<Vec<f64>, CliError>>()?;
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(CliError(format!(
+            "'{flag}' expects three comma separated numbers, got '{value}'"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aov_path, parse_args, CliError, Command, Options};
+    use crate::{
+        image::ImageFormat,
+        integrator::{Aov, IntegratorKind},
+        scene::Scene,
+    };
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        match parse_args(args.iter().map(|arg| arg.to_string()))? {
+            Command::Render(options) => Ok(*options),
+            Command::Help => panic!("unexpected help"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse(&[
+            "scene.json",
+            "--spp=16",
+            "--height",
+            "300",
+            "--aspect-ratio",
+            "3:2",
+            "--look-from",
+            "1,2.5,-3",
+            "-o",
+            "out.ppm",
+        ])
+        .unwrap();
+        assert_eq!(options.scene.unwrap().to_str(), Some("scene.json"));
+        assert_eq!(options.samples_per_pixel, Some(16));
+        assert_eq!(options.height, Some(300));
+        assert_eq!(options.aspect_ratio, Some(1.5));
+        assert_eq!(options.look_from.unwrap().z, -3.0);
+        assert_eq!(options.format, ImageFormat::Ppm);
+
+        assert!(matches!(
+            parse_args(vec!["--help".to_string()]),
+            Ok(Command::Help)
+        ));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(parse(&["--spp", "0"]).is_err());
+        assert!(parse(&["--spp"]).is_err());
+        assert!(parse(&["--width", "-4"]).is_err());
+        assert!(parse(&["--look-at", "1,2"]).is_err());
+        assert_eq!(
+            parse(&["--bogus"]).err(),
+            Some(CliError("unknown option '--bogus'".to_string()))
+        );
+        assert!(parse(&["-o", "image.xyz"]).is_err());
+        assert!(parse(&["a.json", "b.json"]).is_err());
+        assert!(parse(&["--integrator", "photon_mapping"]).is_err());
+        assert!(parse(&["--aov", "normal,bogus"]).is_err());
+        assert!(parse(&["--shutter", "1,0"]).is_err());
+        assert!(parse(&["--shutter", "0.5"]).is_err());
+
+        let sized = ["--width", "400", "--height", "300", "--aspect-ratio"];
+        assert!(parse(&[&sized[..], &["4:3"]].concat()).is_ok());
+        assert_eq!(
+            parse(&[&sized[..], &["2"]].concat()).err(),
+            Some(CliError(
+                "the aspect ratio doesn't match the image size of 400 x 300".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut scene = Scene::random();
+        parse(&[
+            "--height",
+            "90",
+            "--aspect-ratio",
+            "2",
+            "--threads",
+            "3",
+            "--roulette-depth",
+            "0",
+            "--integrator",
+            "whitted",
+            "--aov",
+            "normal,object_id",
+            "--shutter",
+            "0,0.5",
+        ])
+        .unwrap()
+        .apply(&mut scene);
+        assert_eq!(scene.settings.image_height, 90);
+        assert_eq!(scene.settings.image_width, 180);
+        assert_eq!(scene.settings.threads, 3);
+        assert_eq!(scene.settings.roulette_depth, 0);
+        assert_eq!(scene.settings.integrator, IntegratorKind::Whitted);
+        assert_eq!(scene.settings.aovs, vec![Aov::Normal, Aov::ObjectId]);
+        assert_eq!(scene.camera.aspect_ratio, 2.0);
+        assert_eq!(
+            (scene.camera.shutter_open, scene.camera.shutter_close),
+            (0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn test_aov_path() {
+        let path = aov_path(Path::new("renders/out.png"), Aov::Depth);
+        assert_eq!(path, Path::new("renders/out.depth.png"));
+        assert_eq!(aov_path(Path::new("out"), Aov::Uv), Path::new("out.uv"));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{perlin::Perlin, vec3::Vec3};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// How thick a heterogeneous medium is at each point, in collisions per unit
+/// of distance.
+pub trait Density: Send + Sync {
+    fn value(&self, point: &Vec3) -> f64;
+
+    /// Upper bound of `value` everywhere. Delta tracking takes steps as if
+    /// the whole medium were this dense, so the closer the bound, the fewer
+    /// steps are wasted.
+    fn max_value(&self) -> f64;
+}
+
+#[derive(Debug)]
+pub enum GridError {
+    Io { path: PathBuf, error: io::Error },
+    Decode { path: PathBuf, message: String },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            GridError::Decode { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+/// Densities on a regular grid of voxels, with x running fastest, then y,
+/// then z.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    values: Vec<f64>,
+}
+
+impl VoxelGrid {
+    /// `None` unless there is one value per voxel and all of them are
+    /// finite and not negative.
+    pub fn new(resolution: [usize; 3], values: Vec<f64>) -> Option<VoxelGrid> {
+        let count = resolution.iter().product::<usize>();
+        if count == 0
+            || values.len() != count
+            || values
+                .iter()
+                .any(|value| !value.is_finite() || *value < 0.0)
+        {
+            return None;
+        }
+        Some(VoxelGrid { resolution, values })
+    }
+
+    /// Loads a raw grid of little endian 32 bit floats if its `resolution`
+    /// is given, and an ASCII grid otherwise.
+    pub fn load(path: &Path, resolution: Option<[usize; 3]>) -> Result<VoxelGrid, GridError> {
+        let data = fs::read(path).map_err(|error| GridError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let grid = match resolution {
+            Some(resolution) => VoxelGrid::decode_raw(&data, resolution),
+            None => String::from_utf8(data)
+                .map_err(|_| "an ASCII grid must be valid UTF-8".to_string())
+                .and_then(|source| VoxelGrid::parse_ascii(&source)),
+        };
+        grid.map_err(|message| GridError::Decode {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Raw grids are nothing but the values, as little endian 32 bit floats.
+    pub fn decode_raw(data: &[u8], resolution: [usize; 3]) -> Result<VoxelGrid, String> {
+        let count = resolution.iter().product::<usize>();
+        if data.len() != count * 4 {
+            return Err(format!(
+                "expected {} bytes for {} x {} x {} voxels, found {}",
+                count * 4,
+                resolution[0],
+                resolution[1],
+                resolution[2],
+                data.len()
+            ));
+        }
+        let values = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64)
+            .collect();
+        VoxelGrid::new(resolution, values)
+            .ok_or_else(|| "densities must be finite and not negative".to_string())
+    }
+
+    /// ASCII grids start with the number of voxels along x, y and z, followed
+    /// by the values separated by whitespace. `#` starts a comment.
+    pub fn parse_ascii(source: &str) -> Result<VoxelGrid, String> {
+        let mut numbers = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(str::split_whitespace);
+
+        let mut resolution = [0; 3];
+        for size in resolution.iter_mut() {
+            let number = numbers
+                .next()
+                .ok_or_else(|| "expected the resolution of the grid".to_string())?;
+            *size = number
+                .parse()
+                .map_err(|_| format!("invalid grid size '{number}'"))?;
+        }
+        let values = numbers
+            .map(|number| {
+                number
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid density '{number}'"))
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        let count = resolution.iter().product::<usize>();
+        if values.len() != count {
+            return Err(format!(
+                "expected {count} densities for {} x {} x {} voxels, found {}",
+                resolution[0],
+                resolution[1],
+                resolution[2],
+                values.len()
+            ));
+        }
+        VoxelGrid::new(resolution, values)
+            .ok_or_else(|| "densities must be finite and not negative".to_string())
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    fn at(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.values[x + nx * (y + ny * z)]
+    }
+}
+
+/// A voxel grid stretched over the box between `min` and `max`, with its
+/// values scaled by `multiplier`. Values are interpolated between the centers
+/// of the voxels and the medium is empty outside of the box.
+pub struct GridDensity {
+    grid: VoxelGrid,
+    min: Vec3,
+    max: Vec3,
+    multiplier: f64,
+    max_value: f64,
+}
+
+impl GridDensity {
+    pub fn new(grid: VoxelGrid, min: &Vec3, max: &Vec3, multiplier: f64) -> GridDensity {
+        let max_value = grid.values.iter().fold(0.0, |acc: f64, v| acc.max(*v)) * multiplier;
+        GridDensity {
+            grid,
+            min: *min,
+            max: *max,
+            multiplier,
+            max_value,
+        }
+    }
+}
+
+impl Density for GridDensity {
+    fn value(&self, point: &Vec3) -> f64 {
+        let mut cells = [(0, 0, 0.0); 3];
+        for (axis, cell) in cells.iter_mut().enumerate() {
+            let size = self.grid.resolution[axis];
+            let extent = self.max[axis] - self.min[axis];
+            let offset = (point[axis] - self.min[axis]) / extent;
+            if !(0.0..=1.0).contains(&offset) {
+                return 0.0;
+            }
+            // voxel centers are at half steps, clamp towards the edges
+            let position = (offset * size as f64 - 0.5).clamp(0.0, (size - 1) as f64);
+            let low = (position as usize).min(size - 1);
+            *cell = (low, (low + 1).min(size - 1), position - low as f64);
+        }
+
+        let [(x0, x1, tx), (y0, y1, ty), (z0, z1, tz)] = cells;
+        let lerp = |a: f64, b: f64, t: f64| a * (1.0 - t) + b * t;
+        let along_x = |y, z| lerp(self.grid.at(x0, y, z), self.grid.at(x1, y, z), tx);
+        let value = lerp(
+            lerp(along_x(y0, z0), along_x(y1, z0), ty),
+            lerp(along_x(y0, z1), along_x(y1, z1), ty),
+            tz,
+        );
+        value * self.multiplier
+    }
+
+    fn max_value(&self) -> f64 {
+        self.max_value
+    }
+}
+
+/// Billowing clouds from fBm noise at `scale` per unit, in the same way as
+/// `CloudTexture`. `coverage` in `[0, 1]` is about the fraction of space
+/// filled, and `multiplier` the density of the thickest parts.
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,
+    coverage: f64,
+    multiplier: f64,
+}
+
+impl NoiseDensity {
+    pub fn new(scale: f64, coverage: f64, multiplier: f64) -> NoiseDensity {
+        NoiseDensity {
+            noise: Perlin::new(),
+            scale,
+            coverage,
+            multiplier,
+        }
+    }
+}
+
+impl Density for NoiseDensity {
+    fn value(&self, point: &Vec3) -> f64 {
+        let noise = self.noise.fbm(&(*point * self.scale), 6, 2.0, 0.5);
+        ((noise + self.coverage - 0.5) * 4.0).clamp(0.0, 1.0) * self.multiplier
+    }
+
+    fn max_value(&self) -> f64 {
+        self.multiplier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Density, GridDensity, NoiseDensity, VoxelGrid};
+    use crate::{bad_rand, vec3::Vec3};
+
+    #[test]
+    fn test_grid() {
+        // 2 x 1 x 1 voxels, empty on the left and dense on the right
+        let grid = VoxelGrid::parse_ascii("2 1 1 # x y z\n0 4\n").unwrap();
+        assert_eq!(grid.resolution(), [2, 1, 1]);
+        let density = GridDensity::new(
+            grid,
+            &Vec3::new(0.0, 0.0, 0.0),
+            &Vec3::new(2.0, 1.0, 1.0),
+            0.5,
+        );
+        assert_eq!(density.max_value(), 2.0);
+        assert_eq!(density.value(&Vec3::new(0.2, 0.5, 0.5)), 0.0);
+        assert!((density.value(&Vec3::new(1.0, 0.5, 0.5)) - 1.0).abs() < 1e-9);
+        assert_eq!(density.value(&Vec3::new(1.9, 0.1, 0.9)), 2.0);
+        assert_eq!(density.value(&Vec3::new(2.1, 0.5, 0.5)), 0.0);
+
+        let raw: Vec<u8> = [0.0f32, 4.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let grid = VoxelGrid::decode_raw(&raw, [1, 2, 1]).unwrap();
+        assert_eq!(grid.at(0, 1, 0), 4.0);
+        assert!(VoxelGrid::decode_raw(&raw, [1, 1, 1]).is_err());
+        assert_eq!(
+            VoxelGrid::parse_ascii("2 1 1 0").err().unwrap(),
+            "expected 2 densities for 2 x 1 x 1 voxels, found 1"
+        );
+        for value in ["-1", "inf"] {
+            assert_eq!(
+                VoxelGrid::parse_ascii(&format!("1 1 1 {value}"))
+                    .err()
+                    .unwrap(),
+                "densities must be finite and not negative"
+            );
+        }
+        let infinite = f32::INFINITY.to_le_bytes();
+        assert!(VoxelGrid::decode_raw(&infinite, [1, 1, 1]).is_err());
+
+        bad_rand::seed(5);
+        let clouds = NoiseDensity::new(2.0, 0.5, 3.0);
+        let values: Vec<f64> = (0..200)
+            .map(|i| clouds.value(&Vec3::new(i as f64 * 0.05, 0.3, i as f64 * 0.031)))
+            .collect();
+        assert!(values.iter().all(|v| (0.0..=3.0).contains(v)));
+        assert!(values.contains(&0.0) && values.iter().any(|v| *v > 1.0));
+    }
+}
@@ -0,0 +1,119 @@
+use super::{aabb::Aabb, quad::Quad, HitRecord, Hittable};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
+
+/// An axis-aligned box made of six quads whose front faces point out of it.
+pub struct AaBox {
+    faces: [Quad; 6],
+    bounding_box: Aabb,
+}
+
+impl AaBox {
+    /// The box between two opposite corners.
+    pub fn new(a: &Vec3, b: &Vec3, material: Arc<dyn Material>) -> AaBox {
+        let bounding_box = Aabb::new(a, b);
+        let min = *bounding_box.minimum();
+        let max = *bounding_box.maximum();
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+        let faces = [
+            // front, right, back, left, top, bottom
+            Quad::new(&Vec3::new(min.x, min.y, max.z), &dx, &dy, material.clone()),
+            Quad::new(
+                &Vec3::new(max.x, min.y, max.z),
+                &(dz * -1.0),
+                &dy,
+                material.clone(),
+            ),
+            Quad::new(
+                &Vec3::new(max.x, min.y, min.z),
+                &(dx * -1.0),
+                &dy,
+                material.clone(),
+            ),
+            Quad::new(&min, &dz, &dy, material.clone()),
+            Quad::new(
+                &Vec3::new(min.x, max.y, max.z),
+                &dx,
+                &(dz * -1.0),
+                material.clone(),
+            ),
+            Quad::new(&min, &dx, &dz, material),
+        ];
+        AaBox {
+            faces,
+            bounding_box,
+        }
+    }
+
+    pub fn faces(&self) -> &[Quad; 6] {
+        &self.faces
+    }
+}
+
+impl Hittable for AaBox {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_closest_so_far = t_max;
+        let mut rec: Option<HitRecord> = None;
+
+        for face in &self.faces {
+            if let Some(temp_rec) = face.hit(r, t_min, t_closest_so_far) {
+                t_closest_so_far = temp_rec.t_value();
+                rec = Some(temp_rec);
+            }
+        }
+
+        rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounding_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::AaBox;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        let aa_box = AaBox::new(
+            &Vec3::new(1.0, 1.0, 1.0),
+            &Vec3::new(-1.0, -1.0, -1.0),
+            material,
+        );
+        let origin = Vec3::new(0.0, 0.0, -5.0);
+
+        let ray_miss = Ray::new(&origin, &Vec3::new(0.0, 2.0, 5.0));
+        assert!(aa_box.hit(&ray_miss, 0.0, f64::INFINITY).is_none());
+
+        // every face is hit from outside on its front
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for axis in axes {
+            for sign in [-1.0, 1.0] {
+                let offset = Vec3::new(0.1, 0.2, 0.3);
+                let outside = axis * (3.0 * sign) + offset;
+                let ray = Ray::new(&outside, &(axis * -sign));
+                let rec = aa_box.hit(&ray, 0.0, f64::INFINITY).unwrap();
+                assert!((rec.t_value() - (2.0 + sign * offset.dot(&axis))).abs() < 1e-9);
+                assert!(rec.front_face());
+                assert!((rec.normal().dot(&axis) - sign).abs() < 1e-9);
+
+                let inside = Ray::new(&offset, &(axis * sign));
+                let rec = aa_box.hit(&inside, 0.0, f64::INFINITY).unwrap();
+                assert!(!rec.front_face());
+                assert!((rec.normal().dot(&axis) + sign).abs() < 1e-9);
+            }
+        }
+    }
+}
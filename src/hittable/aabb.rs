@@ -0,0 +1,114 @@
+use crate::{ray::Ray, vec3::Vec3};
+
+/// Axis-aligned bounding box used to cull rays before testing the objects it encloses.
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    minimum: Vec3,
+    maximum: Vec3,
+}
+
+impl Aabb {
+    pub fn new(a: &Vec3, b: &Vec3) -> Aabb {
+        Aabb {
+            minimum: Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            maximum: Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn minimum(&self) -> &Vec3 {
+        &self.minimum
+    }
+
+    pub fn maximum(&self) -> &Vec3 {
+        &self.maximum
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.minimum + self.maximum) / 2.0
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            minimum: Vec3::new(
+                self.minimum.x.min(other.minimum.x),
+                self.minimum.y.min(other.minimum.y),
+                self.minimum.z.min(other.minimum.z),
+            ),
+            maximum: Vec3::new(
+                self.maximum.x.max(other.maximum.x),
+                self.maximum.y.max(other.maximum.y),
+                self.maximum.z.max(other.maximum.z),
+            ),
+        }
+    }
+
+    /// Grows the box to contain `point`.
+    pub fn including(&self, point: &Vec3) -> Aabb {
+        self.surrounding(&Aabb::new(point, point))
+    }
+
+    /// Pads degenerate axes so that flat primitives still get a box with volume.
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let extent = self.maximum - self.minimum;
+        let pad = Vec3::new(
+            if extent.x < delta { delta / 2.0 } else { 0.0 },
+            if extent.y < delta { delta / 2.0 } else { 0.0 },
+            if extent.z < delta { delta / 2.0 } else { 0.0 },
+        );
+        Aabb::new(&(self.minimum - pad), &(self.maximum + pad))
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.maximum - self.minimum;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.maximum - self.minimum;
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test against the ray segment between `t_min` and `t_max`.
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / r.direction()[axis];
+            let mut t0 = (self.minimum[axis] - r.origin()[axis]) * inverse_direction;
+            let mut t1 = (self.maximum[axis] - r.origin()[axis]) * inverse_direction;
+            if inverse_direction < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // written so that NaNs from 0 * inf leave the interval untouched
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aabb;
+    use crate::{ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let aabb = Aabb::new(&Vec3::new(1.0, 1.0, 1.0), &Vec3::new(-1.0, -1.0, -1.0));
+        let origin = Vec3::new(0.0, 0.0, 5.0);
+
+        let ray_hit = Ray::new(&origin, &Vec3::new(0.0, 0.0, -1.0));
+        assert!(aabb.hit(&ray_hit, 0.0, f64::INFINITY));
+        assert!(!aabb.hit(&ray_hit, 0.0, 3.0));
+
+        let ray_miss = Ray::new(&origin, &Vec3::new(0.0, 1.0, 0.0));
+        assert!(!aabb.hit(&ray_miss, 0.0, f64::INFINITY));
+    }
+}
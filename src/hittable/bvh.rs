@@ -0,0 +1,328 @@
+use super::{aabb::Aabb, transmittance, HitRecord, Hittable};
+use crate::ray::Ray;
+
+const SAH_BUCKETS: usize = 12;
+const MAX_OBJECTS_PER_LEAF: usize = 4;
+// cost of visiting a node relative to intersecting a single object
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Bounding volume hierarchy over a set of objects, built with a bucketed
+/// surface area heuristic. Objects that are unbounded, and so have no
+/// bounding box, can't be placed in the tree and are tested on every ray
+/// instead.
+pub struct Bvh {
+    root: Option<BvhNode>,
+    unbounded: Vec<Box<dyn Hittable>>,
+}
+
+enum BvhNode {
+    Leaf {
+        bounding_box: Aabb,
+        objects: Vec<Box<dyn Hittable>>,
+    },
+    Branch {
+        bounding_box: Aabb,
+        axis: usize,
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+    },
+}
+
+struct BuildItem {
+    object: Box<dyn Hittable>,
+    bounding_box: Aabb,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Bvh {
+        let mut unbounded = Vec::new();
+        let mut items = Vec::new();
+
+        for object in objects {
+            match object.bounding_box() {
+                Some(bounding_box) => items.push(BuildItem {
+                    object,
+                    bounding_box,
+                }),
+                None => unbounded.push(object),
+            }
+        }
+
+        let root = if items.is_empty() {
+            None
+        } else {
+            Some(BvhNode::build(items))
+        };
+
+        Bvh { root, unbounded }
+    }
+}
+
+impl BvhNode {
+    fn build(mut items: Vec<BuildItem>) -> BvhNode {
+        let bounding_box = items
+            .iter()
+            .skip(1)
+            .fold(items[0].bounding_box, |acc, item| {
+                acc.surrounding(&item.bounding_box)
+            });
+
+        if items.len() <= 1 {
+            return BvhNode::leaf(bounding_box, items);
+        }
+
+        let centroid_bounds = items.iter().skip(1).fold(
+            Aabb::new(
+                &items[0].bounding_box.centroid(),
+                &items[0].bounding_box.centroid(),
+            ),
+            |acc, item| acc.including(&item.bounding_box.centroid()),
+        );
+        let axis = centroid_bounds.longest_axis();
+        let axis_min = centroid_bounds.minimum()[axis];
+        let axis_extent = centroid_bounds.maximum()[axis] - axis_min;
+
+        if axis_extent <= 0.0 {
+            // every centroid is in the same spot, nothing to separate
+            if items.len() <= MAX_OBJECTS_PER_LEAF {
+                return BvhNode::leaf(bounding_box, items);
+            }
+            let right = items.split_off(items.len() / 2);
+            return BvhNode::branch(bounding_box, axis, items, right);
+        }
+
+        let bucket_of = |item: &BuildItem| {
+            let offset = (item.bounding_box.centroid()[axis] - axis_min) / axis_extent;
+            ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut bucket_counts = [0usize; SAH_BUCKETS];
+        let mut bucket_boxes: [Option<Aabb>; SAH_BUCKETS] = [None; SAH_BUCKETS];
+        for item in &items {
+            let bucket = bucket_of(item);
+            bucket_counts[bucket] += 1;
+            bucket_boxes[bucket] = Some(match bucket_boxes[bucket] {
+                Some(b) => b.surrounding(&item.bounding_box),
+                None => item.bounding_box,
+            });
+        }
+
+        // cost of splitting after every bucket but the last
+        let (best_split, best_cost) = (0..SAH_BUCKETS - 1)
+            .map(|split| {
+                let (left_count, left_box) =
+                    merge_buckets(&bucket_counts[..=split], &bucket_boxes[..=split]);
+                let (right_count, right_box) =
+                    merge_buckets(&bucket_counts[split + 1..], &bucket_boxes[split + 1..]);
+                let cost = TRAVERSAL_COST
+                    + (left_count as f64 * left_box.map_or(0.0, |b| b.surface_area())
+                        + right_count as f64 * right_box.map_or(0.0, |b| b.surface_area()))
+                        / bounding_box.surface_area().max(f64::MIN_POSITIVE);
+                (split, cost)
+            })
+            .fold((0, f64::INFINITY), |best, candidate| {
+                if candidate.1 < best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+
+        if items.len() <= MAX_OBJECTS_PER_LEAF && best_cost >= items.len() as f64 {
+            return BvhNode::leaf(bounding_box, items);
+        }
+
+        let (left, right): (Vec<BuildItem>, Vec<BuildItem>) = items
+            .into_iter()
+            .partition(|item| bucket_of(item) <= best_split);
+
+        if left.is_empty() || right.is_empty() {
+            // fall back to a median split when the buckets can't separate the objects
+            let mut items: Vec<BuildItem> = left.into_iter().chain(right).collect();
+            items.sort_by(|a, b| {
+                a.bounding_box.centroid()[axis].total_cmp(&b.bounding_box.centroid()[axis])
+            });
+            let right = items.split_off(items.len() / 2);
+            return BvhNode::branch(bounding_box, axis, items, right);
+        }
+
+        BvhNode::branch(bounding_box, axis, left, right)
+    }
+
+    fn leaf(bounding_box: Aabb, items: Vec<BuildItem>) -> BvhNode {
+        BvhNode::Leaf {
+            bounding_box,
+            objects: items.into_iter().map(|item| item.object).collect(),
+        }
+    }
+
+    fn branch(
+        bounding_box: Aabb,
+        axis: usize,
+        left: Vec<BuildItem>,
+        right: Vec<BuildItem>,
+    ) -> BvhNode {
+        BvhNode::Branch {
+            bounding_box,
+            axis,
+            left: Box::new(BvhNode::build(left)),
+            right: Box::new(BvhNode::build(right)),
+        }
+    }
+
+    fn bounding_box(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounding_box, .. } => bounding_box,
+            BvhNode::Branch { bounding_box, .. } => bounding_box,
+        }
+    }
+
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bounding_box().hit(r, t_min, t_max) {
+            return None;
+        }
+
+        match self {
+            BvhNode::Leaf { objects, .. } => closest_hit(objects, r, t_min, t_max),
+            BvhNode::Branch {
+                axis, left, right, ..
+            } => {
+                // visit the child closer to the ray origin first to tighten t_max sooner
+                let (near, far) = if r.direction()[*axis] >= 0.0 {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                let near_hit = near.hit(r, t_min, t_max);
+                let t_max = near_hit.as_ref().map_or(t_max, |rec| rec.t_value());
+                far.hit(r, t_min, t_max).or(near_hit)
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if !self.bounding_box().hit(r, t_min, t_max) {
+            return 1.0;
+        }
+
+        match self {
+            BvhNode::Leaf { objects, .. } => transmittance(objects, r, t_min, t_max),
+            BvhNode::Branch { left, right, .. } => {
+                let left = left.transmittance(r, t_min, t_max);
+                if left <= 0.0 {
+                    return 0.0;
+                }
+                left * right.transmittance(r, t_min, t_max)
+            }
+        }
+    }
+}
+
+fn merge_buckets(counts: &[usize], boxes: &[Option<Aabb>]) -> (usize, Option<Aabb>) {
+    let count = counts.iter().sum();
+    let bounding_box = boxes.iter().flatten().fold(None, |acc: Option<Aabb>, b| {
+        Some(acc.map_or(*b, |acc| acc.surrounding(b)))
+    });
+    (count, bounding_box)
+}
+
+fn closest_hit(
+    objects: &[Box<dyn Hittable>],
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let mut t_closest_so_far = t_max;
+    let mut rec: Option<HitRecord> = None;
+
+    for obj in objects {
+        if let Some(temp_rec) = obj.hit(r, t_min, t_closest_so_far) {
+            t_closest_so_far = temp_rec.t_value();
+            rec = Some(temp_rec);
+        }
+    }
+
+    rec
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let tree_hit = self
+            .root
+            .as_ref()
+            .and_then(|root| root.hit(r, t_min, t_max));
+        let t_max = tree_hit.as_ref().map_or(t_max, |rec| rec.t_value());
+        closest_hit(&self.unbounded, r, t_min, t_max).or(tree_hit)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let tree = self
+            .root
+            .as_ref()
+            .map_or(1.0, |root| root.transmittance(r, t_min, t_max));
+        if tree <= 0.0 {
+            return 0.0;
+        }
+        tree * transmittance(&self.unbounded, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.root.as_ref().map(|root| *root.bounding_box())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Bvh;
+    use crate::{
+        bad_rand::rand_f64,
+        hittable::{sphere::Sphere, Hittable},
+        material::Lambertian,
+        ray::Ray,
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn test_matches_linear_search() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5)));
+        let random_sphere = || {
+            Sphere::new(
+                &Vec3::new(
+                    rand_f64() * 20.0 - 10.0,
+                    rand_f64() * 20.0 - 10.0,
+                    rand_f64() * 20.0 - 10.0,
+                ),
+                rand_f64() + 0.1,
+                material.clone(),
+            )
+        };
+        let spheres: Vec<Sphere> = (0..200).map(|_| random_sphere()).collect();
+        let objects: Vec<Box<dyn Hittable>> = spheres
+            .iter()
+            .map(|s| {
+                Box::new(Sphere::new(s.center(), s.radius(), material.clone())) as Box<dyn Hittable>
+            })
+            .collect();
+        let bvh = Bvh::new(objects);
+        let world: Vec<Box<dyn Hittable>> = spheres
+            .into_iter()
+            .map(|s| Box::new(s) as Box<dyn Hittable>)
+            .collect();
+
+        for _ in 0..500 {
+            let origin = Vec3::new(rand_f64() * 30.0 - 15.0, rand_f64() * 30.0 - 15.0, 20.0);
+            let direction = Vec3::new(rand_f64() - 0.5, rand_f64() - 0.5, -1.0);
+            let r = Ray::new(&origin, &direction);
+
+            let expected = world.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t_value());
+            let actual = bvh.hit(&r, 0.001, f64::INFINITY).map(|rec| rec.t_value());
+            assert_eq!(expected, actual);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use super::{aabb::Aabb, HitRecord, Hittable};
+use crate::{bad_rand::rand_f64, material::Material, ray::Ray};
+use std::sync::Arc;
+
+/// Fog or smoke of the same density everywhere inside `boundary`. Rays
+/// passing through are scattered at a random distance, so the chance to get
+/// through falls off exponentially with the distance covered inside.
+///
+/// The boundary has to be closed and convex, since a ray is assumed to enter
+/// it once and leave it once.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// `phase_function` decides where light goes once it is scattered, most
+    /// of the time an `Isotropic` material.
+    pub fn new(
+        boundary: Box<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (entry, exit) = span_inside(self.boundary.as_ref(), r, t_min, t_max)?;
+        if self.density <= 0.0 {
+            return None;
+        }
+
+        let distance = -(1.0 - rand_f64()).ln() / self.density;
+        if distance > exit - entry {
+            return None;
+        }
+        Some(scattered(r, entry + distance, &self.phase_function))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match span_inside(self.boundary.as_ref(), r, t_min, t_max) {
+            Some((entry, exit)) => (-self.density * (exit - entry)).exp(),
+            None => 1.0,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Where `r` enters and leaves `boundary`, limited to `t_min` and `t_max`.
+pub(super) fn span_inside(
+    boundary: &dyn Hittable,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64)> {
+    // the boundary is hit behind the origin as well, for rays that start
+    // inside of the medium
+    let entry = boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?.t_value();
+    let exit = boundary.hit(r, entry + 0.0001, f64::INFINITY)?.t_value();
+    let entry = entry.max(t_min);
+    let exit = exit.min(t_max);
+    (entry < exit).then_some((entry, exit))
+}
+
+/// The hit of a ray scattered at `t` inside of a medium.
+pub(super) fn scattered(r: &Ray, t: f64, phase_function: &Arc<dyn Material>) -> HitRecord {
+    // there is no surface, so the normal just faces the ray
+    HitRecord::new(
+        &r.at(t),
+        &(*r.direction() * -1.0),
+        t,
+        true,
+        (0.0, 0.0),
+        phase_function.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::ConstantMedium;
+    use crate::{
+        bad_rand,
+        hittable::{aa_box::AaBox, Hittable},
+        material::Isotropic,
+        ray::Ray,
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn test_hit() {
+        bad_rand::seed(7);
+        let material = Arc::new(Isotropic::new(&Vec3::new(0.8, 0.8, 0.8)));
+        // a slab 2 deep between z = -3 and z = -1
+        let boundary = AaBox::new(
+            &Vec3::new(-10.0, -10.0, -3.0),
+            &Vec3::new(10.0, 10.0, -1.0),
+            material.clone(),
+        );
+        let medium = ConstantMedium::new(Box::new(boundary), 0.5, material);
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 0.0, -1.0));
+
+        let samples = 100000;
+        let mut scattered = 0;
+        for _ in 0..samples {
+            if let Some(rec) = medium.hit(&r, 0.001, f64::INFINITY) {
+                assert!(rec.t_value() >= 1.0 && rec.t_value() <= 3.0);
+                assert!((rec.normal().z - 1.0).abs() < 1e-9);
+                scattered += 1;
+            }
+        }
+        let transmitted = 1.0 - scattered as f64 / samples as f64;
+        assert!((transmitted - (-0.5 * 2.0f64).exp()).abs() < 0.01);
+
+        // starting halfway through, only the rest of the slab is in the way
+        let inside = Ray::new(&Vec3::new(0.0, 0.0, -2.0), &Vec3::new(0.0, 0.0, -1.0));
+        let scattered = (0..samples)
+            .filter(|_| medium.hit(&inside, 0.001, f64::INFINITY).is_some())
+            .count();
+        let transmitted = 1.0 - scattered as f64 / samples as f64;
+        assert!((transmitted - (-0.5f64).exp()).abs() < 0.01);
+        assert!(
+            (medium.transmittance(&inside, 0.001, f64::INFINITY) - (-0.5f64).exp()).abs() < 1e-3
+        );
+
+        assert!(medium.hit(&r, 0.001, 0.5).is_none());
+        assert!(medium.bounding_box().is_some());
+    }
+}
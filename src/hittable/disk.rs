@@ -0,0 +1,120 @@
+use super::{aabb::Aabb, plane, triangle::face_normal, HitRecord, Hittable};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::{f64::consts::PI, sync::Arc};
+
+// padding for the bounding box of disks lying in an axis plane
+const BOUNDING_BOX_PADDING: f64 = 1e-4;
+
+/// A flat disk around `center`, facing along `normal`. `u` goes around the
+/// center and `v` runs from the center to the rim.
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: &Vec3, normal: &Vec3, radius: f64, material: Arc<dyn Material>) -> Disk {
+        Disk {
+            center: *center,
+            normal: normal.normal(),
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self) -> &Vec3 {
+        &self.center
+    }
+
+    pub fn normal(&self) -> &Vec3 {
+        &self.normal
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.material
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = plane::intersect(&self.center, &self.normal, r, t_min, t_max)?;
+        let point = r.at(t);
+        let offset = point - self.center;
+        let distance = offset.length();
+        if distance > self.radius {
+            return None;
+        }
+
+        let (normal, front_face) = face_normal(r, &self.normal, &self.normal);
+        let (u, v) = self.normal.orthonormal_basis();
+        let phi = offset.dot(&v).atan2(offset.dot(&u)) + PI;
+        Some(HitRecord::new(
+            &point,
+            &normal,
+            t,
+            front_face,
+            (phi / (2.0 * PI), distance / self.radius),
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // how far the rim reaches along each axis
+        let extent = Vec3::new(
+            (1.0 - self.normal.x * self.normal.x).max(0.0).sqrt(),
+            (1.0 - self.normal.y * self.normal.y).max(0.0).sqrt(),
+            (1.0 - self.normal.z * self.normal.z).max(0.0).sqrt(),
+        ) * self.radius;
+        Some(
+            Aabb::new(&(self.center - extent), &(self.center + extent))
+                .padded(BOUNDING_BOX_PADDING),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Disk;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        let disk = Disk::new(
+            &Vec3::new(0.0, 0.0, -2.0),
+            &Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            material,
+        );
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        // inside the bounding square, outside the disk
+        let ray_miss = Ray::new(&origin, &Vec3::new(0.8, 0.8, -2.0));
+        assert!(disk.hit(&ray_miss, 0.0, f64::INFINITY).is_none());
+
+        let ray_hit = Ray::new(&origin, &Vec3::new(0.5, 0.0, -2.0));
+        let rec = disk.hit(&ray_hit, 0.0, f64::INFINITY).unwrap();
+        assert!(rec.front_face());
+        assert!((rec.normal().z - 1.0).abs() < 1e-9);
+        let (u, v) = rec.uv();
+        assert!((0.0..=1.0).contains(&u));
+        assert!((v - 0.5).abs() < 1e-9);
+
+        let ray_back = Ray::new(&Vec3::new(0.0, 0.0, -4.0), &Vec3::new(0.0, 0.0, 1.0));
+        let rec = disk.hit(&ray_back, 0.0, f64::INFINITY).unwrap();
+        assert!(!rec.front_face());
+        assert!((rec.normal().z + 1.0).abs() < 1e-9);
+
+        let bounding_box = disk.bounding_box().unwrap();
+        assert!((bounding_box.maximum().x - 1.0).abs() < 1e-9);
+        assert!((bounding_box.maximum().z + 2.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,146 @@
+use super::{
+    aabb::Aabb,
+    constant_medium::{scattered, span_inside},
+    HitRecord, Hittable,
+};
+use crate::{bad_rand::rand_f64, density::Density, material::Material, ray::Ray};
+use std::sync::Arc;
+
+/// Smoke or clouds inside `boundary` whose density varies from point to
+/// point. Collisions are found with delta tracking: the ray takes random
+/// steps as if the medium were as dense as its densest part everywhere, and
+/// each step is a real collision with a chance of the actual density over
+/// that bound. Shadow rays use ratio tracking, which takes the same steps but
+/// multiplies up the chances of getting through instead of deciding at each.
+///
+/// Like for a `ConstantMedium`, the boundary has to be closed and convex.
+pub struct HeterogeneousMedium {
+    boundary: Box<dyn Hittable>,
+    density: Arc<dyn Density>,
+    phase_function: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        boundary: Box<dyn Hittable>,
+        density: Arc<dyn Density>,
+        phase_function: Arc<dyn Material>,
+    ) -> HeterogeneousMedium {
+        HeterogeneousMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+/// Distance to the next tentative collision in a medium of `density`.
+fn step(density: f64) -> f64 {
+    -(1.0 - rand_f64()).ln() / density
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (entry, exit) = span_inside(self.boundary.as_ref(), r, t_min, t_max)?;
+        let max_density = self.density.max_value();
+        if max_density <= 0.0 {
+            return None;
+        }
+
+        let mut t = entry;
+        loop {
+            t += step(max_density);
+            if t >= exit {
+                return None;
+            }
+            if rand_f64() * max_density < self.density.value(&r.at(t)) {
+                return Some(scattered(r, t, &self.phase_function));
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let (entry, exit) = match span_inside(self.boundary.as_ref(), r, t_min, t_max) {
+            Some(span) => span,
+            None => return 1.0,
+        };
+        let max_density = self.density.max_value();
+        if max_density <= 0.0 {
+            return 1.0;
+        }
+
+        let mut transmittance = 1.0;
+        let mut t = entry;
+        loop {
+            t += step(max_density);
+            if t >= exit {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density.value(&r.at(t)) / max_density;
+            // hardly anything gets through anymore, so end the walk at random
+            // and weigh up the walks that go on
+            if transmittance < 0.1 {
+                if rand_f64() < 0.5 {
+                    return 0.0;
+                }
+                transmittance *= 2.0;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::HeterogeneousMedium;
+    use crate::{
+        bad_rand,
+        density::{GridDensity, VoxelGrid},
+        hittable::{aa_box::AaBox, Hittable},
+        material::Isotropic,
+        ray::Ray,
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn test_hit() {
+        bad_rand::seed(11);
+        let material = Arc::new(Isotropic::new(&Vec3::new(0.8, 0.8, 0.8)));
+        // a slab between z = -3 and z = -1, empty in its front half and with
+        // a density of 1 in its back half
+        let (min, max) = (Vec3::new(-10.0, -10.0, -3.0), Vec3::new(10.0, 10.0, -1.0));
+        let grid = VoxelGrid::new([1, 1, 4], vec![1.0, 1.0, 0.0, 0.0]).unwrap();
+        let medium = HeterogeneousMedium::new(
+            Box::new(AaBox::new(&min, &max, material.clone())),
+            Arc::new(GridDensity::new(grid, &min, &max, 1.0)),
+            material,
+        );
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 0.0, -1.0));
+
+        // the density ramps up between the voxel centers at z = -1.75 and
+        // z = -2.25 and stays at 1 from there on, which adds up to 1 unit of
+        // full density to cross
+        let expected = (-1.0f64).exp();
+        let samples = 100000;
+        let mut scattered = 0;
+        let mut ratio_tracked = 0.0;
+        for _ in 0..samples {
+            if let Some(rec) = medium.hit(&r, 0.001, f64::INFINITY) {
+                assert!(rec.t_value() >= 1.75 && rec.t_value() <= 3.0);
+                scattered += 1;
+            }
+            ratio_tracked += medium.transmittance(&r, 0.001, f64::INFINITY) / samples as f64;
+        }
+        let transmitted = 1.0 - scattered as f64 / samples as f64;
+        assert!((transmitted - expected).abs() < 0.01);
+        assert!((ratio_tracked - expected).abs() < 0.01);
+
+        // the empty half lets everything through
+        assert_eq!(medium.transmittance(&r, 0.001, 1.5), 1.0);
+    }
+}
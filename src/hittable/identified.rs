@@ -0,0 +1,31 @@
+use super::{aabb::Aabb, HitRecord, Hittable};
+use crate::ray::Ray;
+
+/// Stamps the hits of `object` with a number that tells the objects of a
+/// scene apart, for the object ID pass.
+pub struct Identified {
+    id: usize,
+    object: Box<dyn Hittable>,
+}
+
+impl Identified {
+    pub fn new(id: usize, object: Box<dyn Hittable>) -> Identified {
+        Identified { id, object }
+    }
+}
+
+impl Hittable for Identified {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.object
+            .hit(r, t_min, t_max)
+            .map(|rec| rec.with_object_id(self.id))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.object.transmittance(r, t_min, t_max)
+    }
+}
@@ -0,0 +1,206 @@
+use super::{aabb::Aabb, HitRecord, Hittable};
+use crate::{
+    material::Material,
+    ray::Ray,
+    transform::{Motion, Transform},
+};
+use std::sync::Arc;
+
+/// Places a shared object somewhere else in the scene. Rays are taken into
+/// the space of the object rather than moving the object itself, so any
+/// number of instances can share one mesh and its BVH.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    placement: Placement,
+    material: Option<Arc<dyn Material>>,
+}
+
+enum Placement {
+    Fixed(Box<Transform>),
+    Moving(Motion),
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance {
+            object,
+            placement: Placement::Fixed(Box::new(transform)),
+            material: None,
+        }
+    }
+
+    /// An instance that moves while the shutter is open, blurring along its
+    /// path.
+    pub fn moving(object: Arc<dyn Hittable>, motion: Motion) -> Instance {
+        Instance {
+            object,
+            placement: Placement::Moving(motion),
+            material: None,
+        }
+    }
+
+    /// Renders the whole instance with `material` instead of the materials of
+    /// the object.
+    pub fn with_material(self, material: Arc<dyn Material>) -> Instance {
+        Instance {
+            material: Some(material),
+            ..self
+        }
+    }
+
+    /// `r` in the space of the object, along with the transform at the time
+    /// of the ray and the object space distance covered per unit of world
+    /// space distance, since rays are normalized in both.
+    fn object_ray(&self, r: &Ray) -> Option<(Transform, Ray, f64)> {
+        let transform = match &self.placement {
+            Placement::Fixed(transform) => **transform,
+            Placement::Moving(motion) => motion.at(r.time()),
+        };
+        let inverse = transform.inverse();
+        let direction = inverse.vector(r.direction());
+        let scale = direction.length();
+        if scale == 0.0 {
+            return None;
+        }
+        let object_ray = Ray::with_time(&inverse.point(r.origin()), &direction, r.time());
+        Some((transform, object_ray, scale))
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (transform, object_ray, scale) = self.object_ray(r)?;
+        let rec = self.object.hit(&object_ray, t_min * scale, t_max * scale)?;
+
+        let t = rec.t_value() / scale;
+        let normal = transform.normal(rec.normal());
+        let rec = rec.with_surface(&r.at(t), &normal, t);
+        Some(match &self.material {
+            Some(material) => rec.with_material(material.clone()),
+            None => rec,
+        })
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self.object_ray(r) {
+            Some((_, object_ray, scale)) => {
+                self.object
+                    .transmittance(&object_ray, t_min * scale, t_max * scale)
+            }
+            None => 1.0,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounding_box = self.object.bounding_box()?;
+        Some(match &self.placement {
+            Placement::Fixed(transform) => transform.bounding_box(&bounding_box),
+            Placement::Moving(motion) => motion.bounding_box(&bounding_box),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Instance;
+    use crate::{
+        hittable::{sphere::Sphere, Hittable},
+        material::Lambertian,
+        ray::Ray,
+        transform::{Motion, Transform},
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn test_hit() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            &Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.8))),
+        ));
+        // an ellipsoid 4 wide, 2 high and deep, centered at (0, 0, -5)
+        let ellipsoid = Instance::new(
+            sphere.clone(),
+            Transform::scaling(&Vec3::new(2.0, 1.0, 1.0))
+                .then(&Transform::translation(&Vec3::new(0.0, 0.0, -5.0))),
+        );
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        let rec = ellipsoid
+            .hit(
+                &Ray::new(&origin, &Vec3::new(0.0, 0.0, -1.0)),
+                0.0,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t_value() - 4.0).abs() < 1e-9);
+        assert!((rec.normal().z - 1.0).abs() < 1e-9);
+        assert!(rec.front_face());
+
+        // along x the ellipsoid reaches twice as far as the sphere
+        let sideways = Ray::new(&Vec3::new(5.0, 0.0, -5.0), &Vec3::new(-1.0, 0.0, 0.0));
+        let rec = ellipsoid.hit(&sideways, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t_value() - 3.0).abs() < 1e-9);
+        assert!((rec.point().x - 2.0).abs() < 1e-9);
+        assert!(ellipsoid.hit(&sideways, 0.0, 2.5).is_none());
+
+        // the normal of a stretched surface leans towards the short axis
+        let diagonal = Ray::new(&Vec3::new(5.0, 5.0, -5.0), &Vec3::new(-1.0, -1.0, 0.0));
+        let rec = ellipsoid.hit(&diagonal, 0.0, f64::INFINITY).unwrap();
+        let (point, normal) = (*rec.point(), *rec.normal());
+        assert!((point.x * point.x / 4.0 + point.y * point.y - 1.0).abs() < 1e-9);
+        let tangent = Vec3::new(-point.y * 4.0, point.x, 0.0);
+        assert!(normal.dot(&tangent).abs() < 1e-9);
+
+        let bounding_box = ellipsoid.bounding_box().unwrap();
+        assert!((bounding_box.maximum().x - 2.0).abs() < 1e-9);
+        assert!((bounding_box.minimum().z + 6.0).abs() < 1e-9);
+
+        let red = Arc::new(Lambertian::new(&Vec3::new(1.0, 0.0, 0.0)));
+        let painted = Instance::new(sphere, Transform::identity()).with_material(red);
+        let rec = painted
+            .hit(
+                &Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0)),
+                0.0,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert_eq!(rec.material().albedo(&rec).y, 0.0);
+    }
+
+    #[test]
+    fn test_moving() {
+        let sphere: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            &Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.8))),
+        ));
+        let moving = Instance::moving(
+            sphere,
+            Motion::new(0.0, 1.0).translate(&Vec3::new(0.0, 0.0, -5.0), &Vec3::new(4.0, 0.0, -5.0)),
+        );
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        let origin = Vec3::new(2.0, 0.0, 0.0);
+
+        assert!(moving
+            .hit(
+                &Ray::with_time(&origin, &direction, 0.0),
+                0.0,
+                f64::INFINITY
+            )
+            .is_none());
+        let rec = moving
+            .hit(
+                &Ray::with_time(&origin, &direction, 0.5),
+                0.0,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t_value() - 4.0).abs() < 1e-9);
+
+        let bounding_box = moving.bounding_box().unwrap();
+        assert!(bounding_box.minimum().x <= -1.0 && bounding_box.maximum().x >= 5.0);
+    }
+}
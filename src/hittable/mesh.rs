@@ -0,0 +1,202 @@
+use super::{
+    aabb::Aabb,
+    bvh::Bvh,
+    triangle::{face_normal, intersect, triangle_bounding_box},
+    HitRecord, Hittable,
+};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
+
+/// Indices of one triangle of a `TriangleMesh` into the shared vertex buffers.
+#[derive(Clone, Copy)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    material: Arc<dyn Material>,
+}
+
+/// Indexed triangle mesh. Faces with normal indices are smooth shaded by
+/// interpolating the vertex normals, the rest use the face normal.
+pub struct TriangleMesh {
+    triangles: Bvh,
+    mesh: Arc<MeshData>,
+    faces: Vec<MeshFace>,
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: MeshFace,
+}
+
+impl TriangleMesh {
+    /// Panics if a face refers to a vertex that doesn't exist.
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        material: Arc<dyn Material>,
+    ) -> TriangleMesh {
+        for face in &faces {
+            let in_range = |indices: Option<[usize; 3]>, len: usize| {
+                indices.is_none_or(|indices| indices.iter().all(|&i| i < len))
+            };
+            assert!(
+                in_range(Some(face.positions), positions.len())
+                    && in_range(face.normals, normals.len())
+                    && in_range(face.uvs, uvs.len()),
+                "mesh face refers to a missing vertex"
+            );
+        }
+
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            material,
+        });
+
+        TriangleMesh {
+            triangles: Bvh::new(
+                faces
+                    .iter()
+                    .map(|&face| {
+                        Box::new(MeshTriangle {
+                            mesh: mesh.clone(),
+                            face,
+                        }) as Box<dyn Hittable>
+                    })
+                    .collect(),
+            ),
+            mesh,
+            faces,
+        }
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.mesh.material
+    }
+
+    /// Corners of every face, in the order the faces were given.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.faces
+            .iter()
+            .map(|face| face.positions.map(|i| self.mesh.positions[i]))
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.triangles.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.triangles.bounding_box()
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [Vec3; 3] {
+        self.face.positions.map(|i| self.mesh.positions[i])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [a, b, c] = self.vertices();
+        let (t, b1, b2) = intersect(&a, &b, &c, r, t_min, t_max)?;
+        let b0 = 1.0 - b1 - b2;
+
+        let geometric_normal = (b - a).cross(&(c - a));
+        let shading_normal = match self.face.normals {
+            Some([n0, n1, n2]) => {
+                let normals = &self.mesh.normals;
+                normals[n0] * b0 + normals[n1] * b1 + normals[n2] * b2
+            }
+            None => geometric_normal,
+        };
+        let (normal, front_face) = face_normal(r, &geometric_normal, &shading_normal);
+
+        let uv = match self.face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.mesh.uvs;
+                (
+                    uvs[t0].0 * b0 + uvs[t1].0 * b1 + uvs[t2].0 * b2,
+                    uvs[t0].1 * b0 + uvs[t1].1 * b1 + uvs[t2].1 * b2,
+                )
+            }
+            None => (b1, b2),
+        };
+
+        Some(HitRecord::new(
+            &r.at(t),
+            &normal,
+            t,
+            front_face,
+            uv,
+            self.mesh.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.vertices()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{MeshFace, TriangleMesh};
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_smooth_shading() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        // unit quad in the z = 0 plane with normals tilted towards +x and -x
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let normals = vec![
+            Vec3::new(-1.0, 0.0, 1.0).normal(),
+            Vec3::new(1.0, 0.0, 1.0).normal(),
+        ];
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let faces = vec![
+            MeshFace {
+                positions: [0, 1, 2],
+                normals: Some([0, 1, 1]),
+                uvs: Some([0, 1, 2]),
+            },
+            MeshFace {
+                positions: [0, 2, 3],
+                normals: Some([0, 1, 0]),
+                uvs: Some([0, 2, 3]),
+            },
+        ];
+        let mesh = TriangleMesh::new(positions, normals, uvs, faces, material);
+
+        let r = Ray::new(&Vec3::new(0.5, 0.25, 1.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert!(rec.front_face());
+        assert!(rec.normal().x.abs() < 1e-9);
+        assert!(rec.normal().z > 0.99);
+        let (u, v) = rec.uv();
+        assert!((u - 0.5).abs() < 1e-9);
+        assert!((v - 0.25).abs() < 1e-9);
+
+        let r = Ray::new(&Vec3::new(0.9, 0.5, 1.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert!(rec.normal().x > 0.5);
+    }
+}
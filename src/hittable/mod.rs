@@ -1,17 +1,109 @@
 use crate::{material::Material, ray::Ray, vec3::Vec3};
-use std::rc::Rc;
+use std::sync::Arc;
 
+pub mod aa_box;
+pub mod aabb;
+pub mod bvh;
+pub mod constant_medium;
+pub mod disk;
+pub mod heterogeneous_medium;
+pub mod identified;
+pub mod instance;
+pub mod mesh;
+pub mod moving_sphere;
+pub mod plane;
+pub mod quad;
 pub mod sphere;
+pub mod triangle;
 
+use aabb::Aabb;
+
+#[derive(Clone)]
 pub struct HitRecord {
     point: Vec3,
     normal: Vec3,
     t_value: f64,
     front_face: bool,
-    material: Rc<dyn Material>,
+    uv: (f64, f64),
+    material: Arc<dyn Material>,
+    object_id: usize,
 }
-pub trait Hittable {
+
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    /// Box enclosing the object, or `None` if it is unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Fraction of the light travelling along `r` that gets through the object
+    /// between `t_min` and `t_max`, for shadow rays. Solid objects block all
+    /// of it wherever they are hit, media only some of it.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.hit(r, t_min, t_max).is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Hittable for Vec<Box<dyn Hittable>> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_closest_so_far = t_max;
+        let mut rec: Option<HitRecord> = None;
+
+        for obj in self {
+            if let Some(temp_rec) = obj.hit(r, t_min, t_closest_so_far) {
+                t_closest_so_far = temp_rec.t_value();
+                rec = Some(temp_rec);
+            }
+        }
+
+        rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut objects = self.iter();
+        let first = objects.next()?.bounding_box()?;
+        objects.try_fold(first, |acc, obj| Some(acc.surrounding(&obj.bounding_box()?)))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        transmittance(self, r, t_min, t_max)
+    }
+}
+
+/// Transmittance through all of `objects`, which stops looking as soon as one
+/// of them blocks the ray.
+pub(super) fn transmittance(
+    objects: &[Box<dyn Hittable>],
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> f64 {
+    let mut transmittance = 1.0;
+    for obj in objects {
+        transmittance *= obj.transmittance(r, t_min, t_max);
+        if transmittance <= 0.0 {
+            break;
+        }
+    }
+    transmittance
+}
+
+/// Shared objects, e.g. a mesh that several instances place in the scene.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.as_ref().hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.as_ref().bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.as_ref().transmittance(r, t_min, t_max)
+    }
 }
 
 impl HitRecord {
@@ -20,17 +112,43 @@ impl HitRecord {
         normal: &Vec3,
         t_value: f64,
         front_face: bool,
-        material: Rc<dyn Material>,
+        uv: (f64, f64),
+        material: Arc<dyn Material>,
     ) -> HitRecord {
         HitRecord {
             point: *point,
             normal: normal.normal(),
             t_value,
             front_face,
+            uv,
             material,
+            object_id: 0,
+        }
+    }
+
+    /// Attributes the hit to the object numbered `id`.
+    pub fn with_object_id(self, id: usize) -> HitRecord {
+        HitRecord {
+            object_id: id,
+            ..self
+        }
+    }
+
+    /// Moves the hit to another place on the ray, e.g. from object space to
+    /// world space.
+    pub fn with_surface(self, point: &Vec3, normal: &Vec3, t_value: f64) -> HitRecord {
+        HitRecord {
+            point: *point,
+            normal: normal.normal(),
+            t_value,
+            ..self
         }
     }
 
+    pub fn with_material(self, material: Arc<dyn Material>) -> HitRecord {
+        HitRecord { material, ..self }
+    }
+
     pub fn t_value(&self) -> f64 {
         self.t_value
     }
@@ -47,7 +165,18 @@ impl HitRecord {
         self.front_face
     }
 
-    pub fn material(&self) -> Rc<dyn Material> {
+    /// Surface coordinates of the hit point, each in `[0, 1]`.
+    pub fn uv(&self) -> (f64, f64) {
+        self.uv
+    }
+
+    pub fn material(&self) -> Arc<dyn Material> {
         self.material.clone()
     }
+
+    /// Number of the object that was hit, 0 unless it is wrapped in an
+    /// `Identified`.
+    pub fn object_id(&self) -> usize {
+        self.object_id
+    }
 }
@@ -0,0 +1,108 @@
+use super::{aabb::Aabb, sphere::hit_sphere, HitRecord, Hittable};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
+
+/// A sphere moving in a straight line from `center0` at `time0` to `center1`
+/// at `time1`. It stands still before and after that.
+pub struct MovingSphere {
+    center0: Vec3,
+    center1: Vec3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: &Vec3,
+        center1: &Vec3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0: *center0,
+            center1: *center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let progress = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + (self.center1 - self.center0) * progress
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        hit_sphere(
+            &self.center(r.time()),
+            self.radius,
+            &self.material,
+            r,
+            t_min,
+            t_max,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = Aabb::new(&(self.center0 - extent), &(self.center0 + extent));
+        let box1 = Aabb::new(&(self.center1 - extent), &(self.center1 + extent));
+        Some(box0.surrounding(&box1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::MovingSphere;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        let sphere = MovingSphere::new(
+            &Vec3::new(0.0, 0.0, -5.0),
+            &Vec3::new(4.0, 0.0, -5.0),
+            0.0,
+            1.0,
+            1.0,
+            material,
+        );
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+
+        let rec = sphere
+            .hit(
+                &Ray::with_time(&origin, &direction, 0.0),
+                0.0,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.t_value() - 4.0).abs() < 1e-9);
+        assert!(sphere
+            .hit(
+                &Ray::with_time(&origin, &direction, 0.5),
+                0.0,
+                f64::INFINITY
+            )
+            .is_none());
+        // stays where it ended up
+        let ahead = Ray::with_time(&Vec3::new(4.0, 0.0, 0.0), &direction, 2.0);
+        assert!(sphere.hit(&ahead, 0.0, f64::INFINITY).is_some());
+
+        let bounding_box = sphere.bounding_box().unwrap();
+        assert_eq!(bounding_box.minimum().x, -1.0);
+        assert_eq!(bounding_box.maximum().x, 5.0);
+    }
+}
@@ -0,0 +1,110 @@
+use super::{aabb::Aabb, triangle::face_normal, HitRecord, Hittable};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
+
+/// An infinite plane through `point`, facing along `normal`. Textures repeat
+/// once per unit along two directions in the plane.
+pub struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: &Vec3, normal: &Vec3, material: Arc<dyn Material>) -> Plane {
+        Plane {
+            point: *point,
+            normal: normal.normal(),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = intersect(&self.point, &self.normal, r, t_min, t_max)?;
+        let point = r.at(t);
+        let (normal, front_face) = face_normal(r, &self.normal, &self.normal);
+        let (u, v) = self.normal.orthonormal_basis();
+        let offset = point - self.point;
+
+        Some(HitRecord::new(
+            &point,
+            &normal,
+            t,
+            front_face,
+            (
+                offset.dot(&u).rem_euclid(1.0),
+                offset.dot(&v).rem_euclid(1.0),
+            ),
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// Ray parameter where `r` crosses the plane through `point` perpendicular to
+/// `normal`, if that is within `t_min..=t_max`.
+pub(super) fn intersect(
+    point: &Vec3,
+    normal: &Vec3,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<f64> {
+    const EPSILON: f64 = 1e-12;
+
+    let denominator = normal.dot(r.direction());
+    if denominator.abs() < EPSILON {
+        // ray is parallel to the plane
+        return None;
+    }
+
+    let t = (*point - *r.origin()).dot(normal) / denominator;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Plane;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5)));
+        let floor = Plane::new(
+            &Vec3::new(0.0, -1.0, 0.0),
+            &Vec3::new(0.0, 2.0, 0.0),
+            material,
+        );
+        assert!(floor.bounding_box().is_none());
+
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let ray_miss = Ray::new(&origin, &Vec3::new(1.0, 0.0, 0.0));
+        assert!(floor.hit(&ray_miss, 0.0, f64::INFINITY).is_none());
+        let ray_up = Ray::new(&origin, &Vec3::new(0.0, 1.0, 0.0));
+        assert!(floor.hit(&ray_up, 0.0, f64::INFINITY).is_none());
+
+        let ray_hit = Ray::new(&origin, &Vec3::new(100.0, -1.0, 0.0));
+        let rec = floor.hit(&ray_hit, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.point().x - 100.0).abs() < 1e-9);
+        assert!(rec.front_face());
+        assert!((rec.normal().y - 1.0).abs() < 1e-9);
+        let (u, v) = rec.uv();
+        assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+
+        let ray_below = Ray::new(&Vec3::new(0.0, -3.0, 0.0), &Vec3::new(0.0, 1.0, 0.0));
+        let rec = floor.hit(&ray_below, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t_value() - 2.0).abs() < 1e-9);
+        assert!(!rec.front_face());
+        assert!((rec.normal().y + 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,122 @@
+use super::{aabb::Aabb, plane, triangle::face_normal, HitRecord, Hittable};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
+
+// padding for the bounding box of quads lying in an axis plane
+const BOUNDING_BOX_PADDING: f64 = 1e-4;
+
+/// A parallelogram spanned by the edges `u` and `v` from `corner`. The front
+/// face is the one `u` x `v` points out of, and the surface coordinates run
+/// along the two edges.
+pub struct Quad {
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // normal scaled so that it turns cross products with the edges into
+    // surface coordinates
+    w: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: &Vec3, u: &Vec3, v: &Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = u.cross(v);
+        Quad {
+            corner: *corner,
+            u: *u,
+            v: *v,
+            normal: n.normal(),
+            w: n / n.length_squared(),
+            material,
+        }
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.material
+    }
+
+    /// The two triangles covering the quad, counter-clockwise seen from the
+    /// front.
+    pub fn triangles(&self) -> [[Vec3; 3]; 2] {
+        let opposite = self.corner + self.u + self.v;
+        [
+            [self.corner, self.corner + self.u, opposite],
+            [self.corner, opposite, self.corner + self.v],
+        ]
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = plane::intersect(&self.corner, &self.normal, r, t_min, t_max)?;
+        let point = r.at(t);
+        let offset = point - self.corner;
+        let alpha = self.w.dot(&offset.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&offset));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let (normal, front_face) = face_normal(r, &self.normal, &self.normal);
+        Some(HitRecord::new(
+            &point,
+            &normal,
+            t,
+            front_face,
+            (alpha, beta),
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(
+            Aabb::new(&self.corner, &(self.corner + self.u + self.v))
+                .including(&(self.corner + self.u))
+                .including(&(self.corner + self.v))
+                .padded(BOUNDING_BOX_PADDING),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Quad;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        // 2 by 1 rectangle in the plane z = -2, facing the origin
+        let quad = Quad::new(
+            &Vec3::new(-1.0, -0.5, -2.0),
+            &Vec3::new(2.0, 0.0, 0.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+            material,
+        );
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        let ray_miss = Ray::new(&origin, &Vec3::new(0.0, 0.6, -2.0));
+        assert!(quad.hit(&ray_miss, 0.0, f64::INFINITY).is_none());
+
+        let ray_hit = Ray::new(&origin, &Vec3::new(0.5, 0.25, -2.0));
+        let rec = quad.hit(&ray_hit, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.point().z + 2.0).abs() < 1e-9);
+        assert!(rec.front_face());
+        assert!((rec.normal().z - 1.0).abs() < 1e-9);
+        let (u, v) = rec.uv();
+        assert!((u - 0.75).abs() < 1e-9);
+        assert!((v - 0.75).abs() < 1e-9);
+
+        let ray_back = Ray::new(&Vec3::new(0.0, 0.0, -4.0), &Vec3::new(0.0, 0.0, 1.0));
+        let rec = quad.hit(&ray_back, 0.0, f64::INFINITY).unwrap();
+        assert!(!rec.front_face());
+        assert!((rec.normal().z + 1.0).abs() < 1e-9);
+
+        let bounding_box = quad.bounding_box().unwrap();
+        assert!(bounding_box.minimum().z < -2.0 && bounding_box.maximum().z > -2.0);
+        assert!((bounding_box.maximum().x - 1.0).abs() < 1e-9);
+    }
+}
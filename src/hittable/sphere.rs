@@ -1,15 +1,15 @@
-use super::{HitRecord, Hittable};
+use super::{aabb::Aabb, HitRecord, Hittable};
 use crate::{material::Material, ray::Ray, vec3::Vec3};
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub struct Sphere {
     center: Vec3,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: &Vec3, radius: f64, material: Rc<dyn Material>) -> Sphere {
+    pub fn new(center: &Vec3, radius: f64, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center: *center,
             radius,
@@ -21,52 +21,82 @@ impl Sphere {
         &self.center
     }
 
-    fn get_normal(&self, point: &Vec3) -> Vec3 {
-        (*point - *self.center()).normal()
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Spherical mapping of a point on the unit sphere. `u` goes around the
+    /// y axis starting at -x and `v` runs from the bottom pole to the top one.
+    pub(crate) fn get_uv(outward_normal: &Vec3) -> (f64, f64) {
+        let theta = (-outward_normal.y).clamp(-1.0, 1.0).acos();
+        let phi = (-outward_normal.z).atan2(outward_normal.x) + std::f64::consts::PI;
+        (
+            phi / (2.0 * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
     }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let origin_to_center = *r.origin() - *self.center();
-        let b_half = origin_to_center.dot(r.direction());
-        let a = r.direction().length_squared();
-        let c = origin_to_center.length_squared() - self.radius * self.radius;
-        let determinant = b_half * b_half - a * c;
-        if determinant >= 0.0 {
-            let determinant_sqrt = determinant.sqrt();
-            let mut t = (-b_half - determinant_sqrt) / a;
-
-            if t < t_min || t > t_max {
-                t = (-b_half + determinant_sqrt) / a;
-            }
-
-            if t >= t_min && t <= t_max {
-                let point = r.at(t);
-                let outward_normal = self.get_normal(&point);
-                let front_face = r.direction().dot(&outward_normal) < 0.0;
-                let normal = if front_face {
-                    outward_normal
-                } else {
-                    outward_normal * -1.0
-                };
-                return Some(HitRecord::new(
-                    &point,
-                    &normal,
-                    t,
-                    front_face,
-                    self.material.clone(),
-                ));
-            }
+        hit_sphere(&self.center, self.radius, &self.material, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(&(self.center - extent), &(self.center + extent)))
+    }
+}
+
+/// Intersection with the sphere around `center`, shared with spheres that
+/// move.
+pub(super) fn hit_sphere(
+    center: &Vec3,
+    radius: f64,
+    material: &Arc<dyn Material>,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord> {
+    let origin_to_center = *r.origin() - *center;
+    let b_half = origin_to_center.dot(r.direction());
+    let a = r.direction().length_squared();
+    let c = origin_to_center.length_squared() - radius * radius;
+    let determinant = b_half * b_half - a * c;
+    if determinant >= 0.0 {
+        let determinant_sqrt = determinant.sqrt();
+        let mut t = (-b_half - determinant_sqrt) / a;
+
+        if t < t_min || t > t_max {
+            t = (-b_half + determinant_sqrt) / a;
         }
 
-        return None;
+        if t >= t_min && t <= t_max {
+            let point = r.at(t);
+            let outward_normal = (point - *center).normal();
+            let front_face = r.direction().dot(&outward_normal) < 0.0;
+            let normal = if front_face {
+                outward_normal
+            } else {
+                outward_normal * -1.0
+            };
+            return Some(HitRecord::new(
+                &point,
+                &normal,
+                t,
+                front_face,
+                Sphere::get_uv(&outward_normal),
+                material.clone(),
+            ));
+        }
     }
+
+    None
 }
 
 #[cfg(test)]
 mod tests {
-    use std::rc::Rc;
+    use std::sync::Arc;
 
     use super::Sphere;
     use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
@@ -77,7 +107,7 @@ mod tests {
         let t_min: f64 = 0.0;
         let t_max: f64 = f64::INFINITY;
 
-        let material = Rc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
         let center = Vec3::new(0.0, 0.0, -5.0);
         let radius: f64 = 2.0;
         let sphere = Sphere::new(&center, radius, material);
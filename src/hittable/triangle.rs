@@ -0,0 +1,153 @@
+use super::{aabb::Aabb, HitRecord, Hittable};
+use crate::{material::Material, ray::Ray, vec3::Vec3};
+use std::sync::Arc;
+
+// padding for the bounding box of triangles lying in an axis plane
+const BOUNDING_BOX_PADDING: f64 = 1e-4;
+
+/// A single flat shaded triangle. Vertices are expected in counter-clockwise
+/// order when looking at the front face.
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(a: &Vec3, b: &Vec3, c: &Vec3, material: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices: [*a, *b, *c],
+            material,
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vec3; 3] {
+        &self.vertices
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.material
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [a, b, c] = &self.vertices;
+        let (t, b1, b2) = intersect(a, b, c, r, t_min, t_max)?;
+        let outward_normal = (*b - *a).cross(&(*c - *a));
+        let (normal, front_face) = face_normal(r, &outward_normal, &outward_normal);
+
+        Some(HitRecord::new(
+            &r.at(t),
+            &normal,
+            t,
+            front_face,
+            (b1, b2),
+            self.material.clone(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(triangle_bounding_box(&self.vertices))
+    }
+}
+
+/// Möller–Trumbore intersection. Returns the ray parameter along with the
+/// barycentric weights of `b` and `c`.
+pub(crate) fn intersect(
+    a: &Vec3,
+    b: &Vec3,
+    c: &Vec3,
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    const EPSILON: f64 = 1e-12;
+
+    let edge1 = *b - *a;
+    let edge2 = *c - *a;
+    let p = r.direction().cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < EPSILON {
+        // ray is parallel to the triangle
+        return None;
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let s = *r.origin() - *a;
+    let b1 = s.dot(&p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(&edge1);
+    let b2 = r.direction().dot(&q) * inverse_determinant;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&q) * inverse_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Orients the shading normal towards the incoming ray. `front_face` is decided
+/// by the geometric normal so interpolated normals can't flip it.
+pub(super) fn face_normal(r: &Ray, geometric_normal: &Vec3, shading_normal: &Vec3) -> (Vec3, bool) {
+    let shading_normal = if shading_normal.dot(geometric_normal) < 0.0 {
+        *shading_normal * -1.0
+    } else {
+        *shading_normal
+    };
+    let front_face = r.direction().dot(geometric_normal) < 0.0;
+    if front_face {
+        (shading_normal, true)
+    } else {
+        (shading_normal * -1.0, false)
+    }
+}
+
+pub(super) fn triangle_bounding_box(vertices: &[Vec3; 3]) -> Aabb {
+    Aabb::new(&vertices[0], &vertices[1])
+        .including(&vertices[2])
+        .padded(BOUNDING_BOX_PADDING)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Triangle;
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray, vec3::Vec3};
+
+    #[test]
+    fn test_hit() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.0)));
+        let triangle = Triangle::new(
+            &Vec3::new(-1.0, -1.0, -2.0),
+            &Vec3::new(1.0, -1.0, -2.0),
+            &Vec3::new(0.0, 1.0, -2.0),
+            material,
+        );
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+
+        let ray_miss = Ray::new(&origin, &Vec3::new(1.0, 1.0, -2.0));
+        assert!(triangle.hit(&ray_miss, 0.0, f64::INFINITY).is_none());
+
+        let ray_hit = Ray::new(&origin, &Vec3::new(0.0, 0.0, -1.0));
+        let rec = triangle.hit(&ray_hit, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t_value() - 2.0).abs() < 1e-9);
+        assert!(rec.front_face());
+        assert!((rec.normal().z - 1.0).abs() < 1e-9);
+        let (u, v) = rec.uv();
+        assert!((u - 0.25).abs() < 1e-9);
+        assert!((v - 0.5).abs() < 1e-9);
+
+        let ray_back = Ray::new(&Vec3::new(0.0, 0.0, -4.0), &Vec3::new(0.0, 0.0, 1.0));
+        let rec = triangle.hit(&ray_back, 0.0, f64::INFINITY).unwrap();
+        assert!(!rec.front_face());
+        assert!((rec.normal().z + 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,237 @@
+//! Single part scanline OpenEXR files with R, G and B channels.
+
+use super::{zlib, HdrImage};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const LINES_PER_ZIP_BLOCK: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrCompression {
+    None,
+    /// zlib over blocks of 16 scanlines.
+    Zip,
+}
+
+impl ExrPixelType {
+    fn id(&self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+
+    fn write(&self, value: f64, out: &mut Vec<u8>) {
+        match self {
+            ExrPixelType::Half => out.extend(f32_to_half(value as f32).to_le_bytes()),
+            ExrPixelType::Float => out.extend((value as f32).to_le_bytes()),
+        }
+    }
+}
+
+impl ExrCompression {
+    fn id(&self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(&self) -> usize {
+        match self {
+            ExrCompression::None => 1,
+            ExrCompression::Zip => LINES_PER_ZIP_BLOCK,
+        }
+    }
+}
+
+pub fn encode(image: &HdrImage, pixel_type: ExrPixelType, compression: ExrCompression) -> Vec<u8> {
+    let width = image.width() as i32;
+    let height = image.height() as i32;
+
+    let mut out = MAGIC.to_vec();
+    // version 2, single part scanline file
+    out.extend(2u32.to_le_bytes());
+
+    let mut channels = Vec::new();
+    // channels are stored in alphabetical order
+    for name in ["B", "G", "R"] {
+        channels.extend(name.as_bytes());
+        channels.push(0);
+        channels.extend(pixel_type.id().to_le_bytes());
+        // pLinear and three reserved bytes, then x and y sampling
+        channels.extend([0, 0, 0, 0]);
+        channels.extend(1i32.to_le_bytes());
+        channels.extend(1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    let window: Vec<u8> = [0, 0, width - 1, height - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    write_attribute(&mut out, "channels", "chlist", &channels);
+    write_attribute(&mut out, "compression", "compression", &[compression.id()]);
+    write_attribute(&mut out, "dataWindow", "box2i", &window);
+    write_attribute(&mut out, "displayWindow", "box2i", &window);
+    // increasing y
+    write_attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    write_attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    write_attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+
+    let lines_per_block = compression.lines_per_block();
+    let blocks: Vec<Vec<u8>> = (0..image.height())
+        .step_by(lines_per_block)
+        .map(|first_line| {
+            let last_line = (first_line + lines_per_block).min(image.height());
+            let mut data = Vec::new();
+            for x in first_line..last_line {
+                for channel in [2, 1, 0] {
+                    for y in 0..image.width() {
+                        pixel_type.write(image.at(x, y)[channel], &mut data);
+                    }
+                }
+            }
+
+            let data = match compression {
+                ExrCompression::None => data,
+                ExrCompression::Zip => {
+                    let compressed = zlib::compress(&zip_predictor(&data));
+                    // blocks that don't shrink are stored as they are
+                    if compressed.len() < data.len() {
+                        compressed
+                    } else {
+                        data
+                    }
+                }
+            };
+
+            let mut block = (first_line as i32).to_le_bytes().to_vec();
+            block.extend((data.len() as i32).to_le_bytes());
+            block.extend(data);
+            block
+        })
+        .collect();
+
+    // offset table pointing at every block from the start of the file
+    let mut offset = (out.len() + blocks.len() * 8) as u64;
+    for block in &blocks {
+        out.extend(offset.to_le_bytes());
+        offset += block.len() as u64;
+    }
+    for block in blocks {
+        out.extend(block);
+    }
+
+    out
+}
+
+fn write_attribute(out: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    out.extend(name.as_bytes());
+    out.push(0);
+    out.extend(attribute_type.as_bytes());
+    out.push(0);
+    out.extend((value.len() as i32).to_le_bytes());
+    out.extend(value);
+}
+
+/// Splits the bytes into even and odd halves and delta encodes them, which
+/// is what the ZIP compression of OpenEXR expects before deflating.
+fn zip_predictor(data: &[u8]) -> Vec<u8> {
+    let mut reordered: Vec<u8> = data.iter().step_by(2).copied().collect();
+    reordered.extend(data.iter().skip(1).step_by(2));
+
+    let mut previous = reordered.first().copied().unwrap_or(0);
+    for value in reordered.iter_mut().skip(1) {
+        let current = *value;
+        *value = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    reordered
+}
+
+/// Converts to IEEE 754 half precision, rounding to nearest even.
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // infinity stays infinity, NaN keeps a non-zero mantissa
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        // subnormal half, shift in the implicit leading one
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // a carry out of the mantissa correctly bumps the exponent, up to infinity
+    sign | (half + round_up as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, f32_to_half, ExrCompression, ExrPixelType};
+    use crate::{image::HdrImage, vec3::Vec3};
+
+    #[test]
+    fn test_half() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(1e6), 0x7c00);
+        assert_eq!(f32_to_half(f32::NAN) & 0x7c00, 0x7c00);
+        assert_eq!(f32_to_half(5.960_464_5e-8), 0x0001);
+        assert_eq!(f32_to_half(0.333_333_34), 0x3555);
+    }
+
+    #[test]
+    fn test_layout() {
+        let image = HdrImage::new(3, 2, vec![Vec3::new(1.0, 2.0, 3.0); 6]);
+        let data = encode(&image, ExrPixelType::Float, ExrCompression::None);
+        assert_eq!(&data[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        // three blocks of one line, each: y, size, then B, G and R for 2 pixels
+        let block_len = 4 + 4 + 3 * 2 * 4;
+        let blocks_start = data.len() - 3 * block_len;
+        let first_offset = u64::from_le_bytes(
+            data[blocks_start - 24..blocks_start - 16]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(first_offset as usize, blocks_start);
+
+        let block = &data[blocks_start..blocks_start + block_len];
+        assert_eq!(&block[..8], &[0, 0, 0, 0, 24, 0, 0, 0]);
+        assert_eq!(&block[8..12], &3f32.to_le_bytes());
+        assert_eq!(&block[24..28], &1f32.to_le_bytes());
+    }
+}
@@ -0,0 +1,298 @@
+//! Radiance RGBE (`.hdr`) files.
+
+use super::{check_size, HdrImage};
+use crate::vec3::Vec3;
+
+// scanlines outside these widths can't use run length encoding
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+const MIN_RUN: usize = 4;
+const MAX_RUN: usize = 127;
+const MAX_LITERALS: usize = 128;
+
+/// Encodes the image as run length encoded RGBE scanlines, top row first.
+pub fn encode(image: &HdrImage) -> Vec<u8> {
+    let mut out = format!(
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height(),
+        image.width()
+    )
+    .into_bytes();
+
+    for x in 0..image.height() {
+        let scanline: Vec<[u8; 4]> = (0..image.width())
+            .map(|y| to_rgbe(image.at(x, y)))
+            .collect();
+        if !(MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&image.width()) {
+            out.extend(scanline.iter().flatten());
+            continue;
+        }
+
+        out.extend([2, 2, (image.width() >> 8) as u8, image.width() as u8]);
+        for channel in 0..4 {
+            let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[channel]).collect();
+            encode_runs(&values, &mut out);
+        }
+    }
+
+    out
+}
+
+/// Decodes a Radiance file into linear colors. Only the usual `-Y H +X W`
+/// orientation is supported.
+pub fn decode(data: &[u8]) -> Result<HdrImage, String> {
+    let mut position = 0;
+    let mut next_line = || {
+        let start = position;
+        let end = data[start..]
+            .iter()
+            .position(|&c| c == b'\n')
+            .map(|i| start + i)
+            .ok_or("Radiance header is truncated")?;
+        position = end + 1;
+        Ok::<_, String>(String::from_utf8_lossy(&data[start..end]).into_owned())
+    };
+
+    let magic = next_line()?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return Err("not a Radiance file".to_string());
+    }
+    // header variables up to an empty line
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("unsupported Radiance format '{format}'"));
+            }
+        }
+    }
+
+    let resolution = next_line()?;
+    let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (height.parse(), width.parse()),
+        _ => return Err(format!("unsupported Radiance orientation '{resolution}'")),
+    };
+    let (height, width): (usize, usize) = match (height, width) {
+        (Ok(height), Ok(width)) => (height, width),
+        _ => return Err(format!("invalid Radiance resolution '{resolution}'")),
+    };
+    check_size("Radiance", width, height)?;
+
+    let mut reader = &data[position..];
+    let mut pixels = Vec::with_capacity(width * height);
+    for _ in 0..height {
+        let scanline = decode_scanline(&mut reader, width)?;
+        pixels.extend(scanline.into_iter().map(from_rgbe));
+    }
+    Ok(HdrImage::new(height, width, pixels))
+}
+
+fn decode_scanline(reader: &mut &[u8], width: usize) -> Result<Vec<[u8; 4]>, String> {
+    let mut scanline = vec![[0u8; 4]; width];
+    let marker = reader.get(..4).ok_or("Radiance image data is truncated")?;
+    let is_rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width)
+        && marker[0] == 2
+        && marker[1] == 2
+        && marker[2] & 0x80 == 0;
+    if !is_rle {
+        // flat pixels, possibly with old style runs repeating the previous pixel
+        let mut x = 0;
+        let mut shift = 0;
+        while x < width {
+            let rgbe: [u8; 4] = take(reader, 4)?.try_into().unwrap();
+            if rgbe[..3] == [1, 1, 1] && x > 0 {
+                let count = (rgbe[3] as usize) << shift;
+                for _ in 0..count.min(width - x) {
+                    scanline[x] = scanline[x - 1];
+                    x += 1;
+                }
+                shift += 8;
+            } else {
+                scanline[x] = rgbe;
+                x += 1;
+                shift = 0;
+            }
+        }
+        return Ok(scanline);
+    }
+
+    if ((marker[2] as usize) << 8 | marker[3] as usize) != width {
+        return Err("Radiance scanline length mismatch".to_string());
+    }
+    take(reader, 4)?;
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = take(reader, 1)?[0] as usize;
+            if count > 128 {
+                let count = count - 128;
+                let value = take(reader, 1)?[0];
+                if x + count > width {
+                    return Err("Radiance run overflows the scanline".to_string());
+                }
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value;
+                }
+                x += count;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err("invalid Radiance literal span".to_string());
+                }
+                for (pixel, &value) in scanline[x..x + count].iter_mut().zip(take(reader, count)?) {
+                    pixel[channel] = value;
+                }
+                x += count;
+            }
+        }
+    }
+    Ok(scanline)
+}
+
+/// Splits off the first `count` bytes of `reader`.
+fn take<'a>(reader: &mut &'a [u8], count: usize) -> Result<&'a [u8], String> {
+    let bytes = reader
+        .get(..count)
+        .ok_or("Radiance image data is truncated")?;
+    *reader = &reader[count..];
+    Ok(bytes)
+}
+
+/// Shared exponent encoding, see Greg Ward's "Real Pixels" in Graphics Gems II.
+pub fn to_rgbe(color: &Vec3) -> [u8; 4] {
+    let v = color.x.max(color.y).max(color.z);
+    if v.is_nan() || v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let v = v.min(f32::MAX as f64);
+    let (mantissa, exponent) = frexp(v);
+    let scale = mantissa * 256.0 / v;
+    let channel = |c: f64| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        channel(color.x),
+        channel(color.y),
+        channel(color.z),
+        (exponent + 128) as u8,
+    ]
+}
+
+pub fn from_rgbe(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Vec3::new(
+        (rgbe[0] as f64 + 0.5) * scale,
+        (rgbe[1] as f64 + 0.5) * scale,
+        (rgbe[2] as f64 + 0.5) * scale,
+    )
+}
+
+/// Splits a positive `v` into a mantissa in `[0.5, 1)` and a power of two.
+fn frexp(v: f64) -> (f64, i32) {
+    let mut exponent = v.log2().floor() as i32 + 1;
+    let mut mantissa = v / 2f64.powi(exponent);
+    // log2 can be off by one ulp around powers of two
+    if mantissa >= 1.0 {
+        mantissa /= 2.0;
+        exponent += 1;
+    } else if mantissa < 0.5 {
+        mantissa *= 2.0;
+        exponent -= 1;
+    }
+    (mantissa, exponent)
+}
+
+/// Writes one channel of a scanline as runs (count + 128, value) and literal
+/// spans (count, values...).
+fn encode_runs(values: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < values.len() {
+        // find the next run that is worth encoding
+        let mut run_start = i;
+        let mut run_len = 0;
+        while run_start < values.len() {
+            run_len = values[run_start..]
+                .iter()
+                .take(MAX_RUN)
+                .take_while(|&&v| v == values[run_start])
+                .count();
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+        if run_len < MIN_RUN {
+            run_start = values.len();
+        }
+
+        for literals in values[i..run_start].chunks(MAX_LITERALS) {
+            out.push(literals.len() as u8);
+            out.extend(literals);
+        }
+        if run_start < values.len() {
+            out.push(128 + run_len as u8);
+            out.push(values[run_start]);
+        }
+        i = run_start + if run_start < values.len() { run_len } else { 0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, encode_runs, from_rgbe, to_rgbe};
+    use crate::{image::HdrImage, vec3::Vec3};
+
+    #[test]
+    fn test_rgbe() {
+        assert_eq!(to_rgbe(&Vec3::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(&Vec3::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+
+        let color = Vec3::new(1234.5, 0.25, 617.0);
+        let decoded = from_rgbe(to_rgbe(&color));
+        assert!((decoded.x - color.x).abs() / color.x < 0.01);
+        assert!((decoded.z - color.z).abs() / color.z < 0.01);
+    }
+
+    #[test]
+    fn test_runs() {
+        let mut out = Vec::new();
+        encode_runs(&[1, 2, 3, 3, 3, 3, 3, 4, 4], &mut out);
+        assert_eq!(out, vec![2, 1, 2, 128 + 5, 3, 2, 4, 4]);
+    }
+
+    #[test]
+    fn test_header() {
+        let image = HdrImage::new(2, 10, vec![Vec3::new(0.5, 0.5, 0.5); 20]);
+        let data = encode(&image);
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 10\n";
+        assert_eq!(&data[..header.len()], header);
+        // per scanline: marker and one run for each of the four channels
+        assert_eq!(data.len(), header.len() + 2 * (4 + 4 * 2));
+    }
+
+    #[test]
+    fn test_decode() {
+        for width in [3, 40] {
+            let pixels: Vec<Vec3> = (0..2 * width)
+                .map(|i| Vec3::new(i as f64 * 10.0, 0.5, if i < 20 { 0.0 } else { 2.0 }))
+                .collect();
+            let image = HdrImage::new(2, width, pixels);
+            let decoded = decode(&encode(&image)).unwrap();
+            assert_eq!((decoded.height(), decoded.width()), (2, width));
+            for (a, b) in image.pixels().iter().zip(decoded.pixels()) {
+                let expected = from_rgbe(to_rgbe(a));
+                assert_eq!((expected.x, expected.y, expected.z), (b.x, b.y, b.z));
+            }
+        }
+
+        // old style run length encoding repeating the first pixel twice
+        let data = b"#?RADIANCE\n\n-Y 1 +X 3\n\x80\x40\x00\x81\x01\x01\x01\x02";
+        let decoded = decode(data).unwrap();
+        assert_eq!(decoded.at(0, 2).x, decoded.at(0, 0).x);
+        assert_eq!(decoded.at(0, 1).y, decoded.at(0, 0).y);
+        assert!(decode(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n").is_err());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::vec3::Vec3;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub mod exr;
+pub mod hdr;
+pub mod png;
+pub mod ppm;
+pub mod zlib;
+
+use exr::{ExrCompression, ExrPixelType};
+use png::PngColor;
+
+/// File formats images can be written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ImageFormat {
+    #[default]
+    Ppm,
+    Png,
+    /// PNG with 16 bits per channel.
+    Png16,
+    /// Radiance RGBE
+    Hdr,
+    /// OpenEXR with half float channels
+    Exr,
+    /// OpenEXR with 32 bit float channels
+    Exr32,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "png16" => Some(ImageFormat::Png16),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            "exr32" => Some(ImageFormat::Exr32),
+            _ => None,
+        }
+    }
+
+    /// Whether the format keeps values outside of `[0, 1]`.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, ImageFormat::Hdr | ImageFormat::Exr | ImageFormat::Exr32)
+    }
+
+    /// Picks the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        ImageFormat::from_name(path.extension()?.to_str()?)
+    }
+}
+
+#[derive(Clone)]
+pub struct Pixel {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Pixel {
+    pub fn new(r: u8, g: u8, b: u8) -> Pixel {
+        Pixel { r, g, b }
+    }
+
+    pub fn from_vec3(v: &Vec3) -> Pixel {
+        Pixel::new(Pixel::clamp(v.x), Pixel::clamp(v.y), Pixel::clamp(v.z))
+    }
+
+    /// Quantizes a linear color, where 1.0 is full intensity, applying gamma 2
+    /// correction. Anything brighter than 1.0 is clipped.
+    pub fn from_color(color: &Vec3) -> Pixel {
+        Pixel::from_vec3(&(color.sqrt() * 256.0))
+    }
+
+    /// Inverse of `from_color`, mapping each value to the middle of the
+    /// range of colors that quantize to it.
+    pub fn to_color(&self) -> Vec3 {
+        let linear = |c: u8| ((c as f64 + 0.5) / 256.0).powi(2);
+        Vec3::new(linear(self.r), linear(self.g), linear(self.b))
+    }
+
+    fn clamp(v: f64) -> u8 {
+        v.clamp(0.0, 255.0) as u8
+    }
+
+    pub fn black() -> Pixel {
+        Pixel::new(0, 0, 0)
+    }
+}
+
+pub struct Image {
+    height: usize,
+    width: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Image {
+    pub fn new(height: usize, width: usize, pixels: Vec<Pixel>) -> Image {
+        Image {
+            height,
+            width,
+            pixels,
+        }
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> &Pixel {
+        let index = x * self.width + y;
+        &self.pixels[index]
+    }
+
+    pub fn to_ppm(&self) -> String {
+        let h_range = 0..self.height;
+        let w_range = 0..self.width;
+
+        let body: String = h_range
+            .flat_map(|x| w_range.clone().map(move |y| (x, y)))
+            .map(|(x, y)| self.at(x, y))
+            .map(|pixel| format!("{} {} {}\n", pixel.r, pixel.g, pixel.b))
+            .collect();
+
+        format!("P3\n{} {}\n255\n {body}", self.width, self.height)
+    }
+
+    /// Encodes the image as PNG. The alpha channel, if requested, is opaque and
+    /// 16 bit samples repeat the 8 bit value in both bytes; use
+    /// `HdrImage::to_png` for real 16 bit precision.
+    pub fn to_png(&self, color: PngColor, bit_depth: u8) -> Vec<u8> {
+        let scale = if bit_depth == 16 { 257 } else { 1 };
+        let samples: Vec<u16> = self
+            .pixels
+            .iter()
+            .flat_map(|pixel| {
+                let rgb = [pixel.r, pixel.g, pixel.b].map(|v| v as u16 * scale);
+                match color {
+                    PngColor::Rgb => rgb.to_vec(),
+                    PngColor::Rgba => vec![rgb[0], rgb[1], rgb[2], 255 * scale],
+                }
+            })
+            .collect();
+        png::encode(self.width, self.height, color, bit_depth, &samples)
+    }
+
+    pub fn to_js_image_data(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|pixel| vec![pixel.r, pixel.g, pixel.b, 255]).collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io { path: PathBuf, error: io::Error },
+    Decode { path: PathBuf, message: String },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            ImageError::Decode { path, message } => write!(f, "{}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// How lookups between pixel centers are resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+/// What lookups outside of `[0, 1]` see.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    /// The image tiles the plane.
+    #[default]
+    Repeat,
+    /// The edge pixels extend forever.
+    Clamp,
+}
+
+impl Wrap {
+    fn index(&self, i: i64, size: usize) -> usize {
+        match self {
+            Wrap::Repeat => i.rem_euclid(size as i64) as usize,
+            Wrap::Clamp => i.clamp(0, size as i64 - 1) as usize,
+        }
+    }
+}
+
+/// Image of unclamped linear radiance, as it comes out of the renderer.
+pub struct HdrImage {
+    height: usize,
+    width: usize,
+    pixels: Vec<Vec3>,
+}
+
+/// Largest number of pixels of images read from files, so that a broken
+/// header can't have the decoders allocate gigabytes.
+const MAX_DECODED_PIXELS: usize = 1 << 26;
+
+/// Checks the size of an image to decode, `kind` names its format in errors.
+fn check_size(kind: &str, width: usize, height: usize) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!("{kind} image is empty"));
+    }
+    if width.saturating_mul(height) > MAX_DECODED_PIXELS {
+        return Err(format!(
+            "{kind} image of {width} x {height} pixels is too large"
+        ));
+    }
+    Ok(())
+}
+
+impl HdrImage {
+    pub fn new(height: usize, width: usize, pixels: Vec<Vec3>) -> HdrImage {
+        assert_eq!(pixels.len(), height * width);
+        HdrImage {
+            height,
+            width,
+            pixels,
+        }
+    }
+
+    /// Reads a PNG, PPM or Radiance file, see `decode`.
+    pub fn load(path: &Path) -> Result<HdrImage, ImageError> {
+        let data = fs::read(path).map_err(|error| ImageError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        HdrImage::decode(&data).map_err(|message| ImageError::Decode {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Decodes a PNG, PPM or Radiance file, recognized by their signature,
+    /// into linear colors. PNG and PPM files are taken to be gamma 2 encoded,
+    /// the inverse of how images are written.
+    pub fn decode(data: &[u8]) -> Result<HdrImage, String> {
+        let mut image = if data.starts_with(&png::SIGNATURE) {
+            png::decode(data)?
+        } else if data.starts_with(b"P3") || data.starts_with(b"P6") {
+            ppm::decode(data)?
+        } else if data.starts_with(b"#?") {
+            return hdr::decode(data);
+        } else {
+            return Err("unrecognized image format, expected PNG, PPM or Radiance".to_string());
+        };
+        for pixel in &mut image.pixels {
+            *pixel = pixel.component_mul(pixel);
+        }
+        Ok(image)
+    }
+
+    /// Undoes the gamma correction of an image meant for display.
+    pub fn from_image(image: &Image) -> HdrImage {
+        HdrImage::new(
+            image.height,
+            image.width,
+            image.pixels.iter().map(Pixel::to_color).collect(),
+        )
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Pixel in row `x` (counting from the top) and column `y`, like `Image::at`.
+    pub fn at(&self, x: usize, y: usize) -> &Vec3 {
+        &self.pixels[x * self.width + y]
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    /// Looks up the image with `(0, 0)` at the bottom left corner and `(1, 1)`
+    /// at the top right.
+    pub fn sample(&self, u: f64, v: f64, filter: Filter, wrap: Wrap) -> Vec3 {
+        let (u, v) = (finite_or_zero(u), finite_or_zero(v));
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+        let pixel = |x: i64, y: i64| {
+            *self.at(wrap.index(y, self.height), wrap.index(x, self.width))
+        };
+
+        match filter {
+            Filter::Nearest => pixel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // blend the four pixels whose centers surround the point
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = pixel(x0, y0) * (1.0 - tx) + pixel(x0 + 1, y0) * tx;
+                let bottom = pixel(x0, y0 + 1) * (1.0 - tx) + pixel(x0 + 1, y0 + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+
+    /// Gamma corrects and quantizes the image for display.
+    pub fn to_image(&self) -> Image {
+        Image::new(
+            self.height,
+            self.width,
+            self.pixels.iter().map(Pixel::from_color).collect(),
+        )
+    }
+
+    /// Encodes the gamma corrected image as PNG with 8 or 16 bits per channel.
+    pub fn to_png(&self, color: PngColor, bit_depth: u8) -> Vec<u8> {
+        self.png_with(color, bit_depth, f64::sqrt)
+    }
+
+    /// Like `encode`, but PPM and PNG files get the values as they are,
+    /// without gamma correction, for passes that hold data rather than colors.
+    pub fn encode_linear(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Ppm => {
+                let pixels = self.pixels.iter().map(|p| Pixel::from_vec3(&(*p * 256.0)));
+                Image::new(self.height, self.width, pixels.collect()).to_ppm().into_bytes()
+            }
+            ImageFormat::Png => self.png_with(PngColor::Rgb, 8, |v| v),
+            ImageFormat::Png16 => self.png_with(PngColor::Rgb, 16, |v| v),
+            _ => self.encode(format),
+        }
+    }
+
+    /// `transfer` maps linear values in `[0, 1]` to the ones stored.
+    fn png_with(&self, color: PngColor, bit_depth: u8, transfer: fn(f64) -> f64) -> Vec<u8> {
+        let max = if bit_depth == 16 { 65535.0 } else { 255.0 };
+        let quantize = |v: f64| (transfer(v.max(0.0)) * (max + 1.0)).clamp(0.0, max) as u16;
+        let samples: Vec<u16> = self
+            .pixels
+            .iter()
+            .flat_map(|p| {
+                let rgb = [quantize(p.x), quantize(p.y), quantize(p.z)];
+                match color {
+                    PngColor::Rgb => rgb.to_vec(),
+                    PngColor::Rgba => vec![rgb[0], rgb[1], rgb[2], max as u16],
+                }
+            })
+            .collect();
+        png::encode(self.width, self.height, color, bit_depth, &samples)
+    }
+
+    pub fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Ppm => self.to_image().to_ppm().into_bytes(),
+            ImageFormat::Png => self.to_png(PngColor::Rgb, 8),
+            ImageFormat::Png16 => self.to_png(PngColor::Rgb, 16),
+            ImageFormat::Hdr => hdr::encode(self),
+            ImageFormat::Exr => exr::encode(self, ExrPixelType::Half, ExrCompression::Zip),
+            ImageFormat::Exr32 => exr::encode(self, ExrPixelType::Float, ExrCompression::Zip),
+        }
+    }
+}
+
+fn finite_or_zero(v: f64) -> f64 {
+    if v.is_finite() {
+        v
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, HdrImage, ImageFormat, Wrap};
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn test_sample() {
+        // one row: 0 and 1
+        let image = HdrImage::new(1, 2, vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)]);
+        let sample = |u: f64, filter, wrap| image.sample(u, 0.5, filter, wrap).x;
+
+        assert_eq!(sample(0.2, Filter::Nearest, Wrap::Repeat), 0.0);
+        assert_eq!(sample(0.7, Filter::Nearest, Wrap::Repeat), 1.0);
+        assert_eq!(sample(1.2, Filter::Nearest, Wrap::Repeat), 0.0);
+        assert_eq!(sample(1.2, Filter::Nearest, Wrap::Clamp), 1.0);
+
+        // pixel centers are at u = 0.25 and 0.75
+        assert_eq!(sample(0.25, Filter::Bilinear, Wrap::Clamp), 0.0);
+        assert_eq!(sample(0.5, Filter::Bilinear, Wrap::Clamp), 0.5);
+        assert_eq!(sample(0.95, Filter::Bilinear, Wrap::Clamp), 1.0);
+        assert!((sample(0.95, Filter::Bilinear, Wrap::Repeat) - 0.6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_decode() {
+        let image = HdrImage::decode(b"P3 1 1 4 2 4 0").unwrap();
+        assert_eq!(image.at(0, 0).x, 0.25);
+        assert_eq!(image.at(0, 0).y, 1.0);
+        assert!(HdrImage::decode(b"GIF89a").is_err());
+
+        // passes that hold data are written without gamma
+        let image = HdrImage::new(1, 1, vec![Vec3::new(0.25, 0.5, 1.0)]);
+        let ppm = |data: Vec<u8>| String::from_utf8(data).unwrap();
+        assert!(ppm(image.encode(ImageFormat::Ppm)).ends_with(" 128 181 255\n"));
+        assert!(ppm(image.encode_linear(ImageFormat::Ppm)).ends_with(" 64 128 255\n"));
+        assert_eq!(
+            HdrImage::decode(b"P3 0 0 255").err().unwrap(),
+            "PPM image is empty"
+        );
+        assert_eq!(
+            HdrImage::decode(b"P6 100000 100000 255 ").err().unwrap(),
+            "PPM image of 100000 x 100000 pixels is too large"
+        );
+        assert!(HdrImage::decode(b"#?RADIANCE\n\n-Y 0 +X 5\n").is_err());
+    }
+}
@@ -0,0 +1,410 @@
+use super::{check_size, zlib, HdrImage};
+use crate::vec3::Vec3;
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// origin and spacing of the pixels in each of the seven Adam7 passes
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PngColor {
+    Rgb,
+    Rgba,
+}
+
+impl PngColor {
+    fn channels(&self) -> usize {
+        match self {
+            PngColor::Rgb => 3,
+            PngColor::Rgba => 4,
+        }
+    }
+
+    fn color_type(&self) -> u8 {
+        match self {
+            PngColor::Rgb => 2,
+            PngColor::Rgba => 6,
+        }
+    }
+}
+
+/// Encodes an image given as rows of interleaved samples, top row first.
+/// `bit_depth` is either 8 or 16; for 8 bits only the low byte of each sample
+/// is used.
+pub fn encode(
+    width: usize,
+    height: usize,
+    color: PngColor,
+    bit_depth: u8,
+    samples: &[u16],
+) -> Vec<u8> {
+    assert!(
+        bit_depth == 8 || bit_depth == 16,
+        "unsupported PNG bit depth {bit_depth}"
+    );
+    assert_eq!(samples.len(), width * height * color.channels());
+
+    let bytes_per_sample = bit_depth as usize / 8;
+    let bytes_per_pixel = color.channels() * bytes_per_sample;
+    let row_len = width * bytes_per_pixel;
+
+    let raw: Vec<u8> = if bit_depth == 8 {
+        samples.iter().map(|&s| s as u8).collect()
+    } else {
+        samples.iter().flat_map(|s| s.to_be_bytes()).collect()
+    };
+
+    let mut filtered = Vec::with_capacity((row_len + 1) * height);
+    let empty_row = vec![0; row_len];
+    for y in 0..height {
+        let row = &raw[y * row_len..(y + 1) * row_len];
+        let previous = if y == 0 {
+            &empty_row[..]
+        } else {
+            &raw[(y - 1) * row_len..y * row_len]
+        };
+        filter_row(row, previous, bytes_per_pixel, &mut filtered);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // bit depth, color type, compression, filter method, no interlacing
+    header.extend([bit_depth, color.color_type(), 0, 0, 0]);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib::compress(&filtered));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Decodes any standard PNG into an image with samples scaled to `[0, 1]`,
+/// still gamma encoded. Grayscale is expanded to RGB and alpha is dropped.
+pub fn decode(data: &[u8]) -> Result<HdrImage, String> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err("not a PNG file".to_string());
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut position = SIGNATURE.len();
+    loop {
+        let truncated = || "PNG file is truncated".to_string();
+        let length = data
+            .get(position..position + 4)
+            .ok_or_else(truncated)?
+            .try_into()
+            .map(u32::from_be_bytes)
+            .unwrap() as usize;
+        let chunk = data
+            .get(position + 4..position + 12 + length)
+            .ok_or_else(truncated)?;
+        let (chunk_type, rest) = chunk.split_at(4);
+        let (chunk_data, crc) = rest.split_at(length);
+        if zlib::crc32(&chunk[..4 + length]).to_be_bytes() != crc {
+            return Err(format!(
+                "CRC mismatch in the {} chunk",
+                String::from_utf8_lossy(chunk_type)
+            ));
+        }
+        position += 12 + length;
+
+        match chunk_type {
+            b"IHDR" => header = Some(Header::parse(chunk_data)?),
+            b"PLTE" => {
+                palette = chunk_data
+                    .chunks_exact(3)
+                    .map(|rgb| Vec3::new(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64) / 255.0)
+                    .collect()
+            }
+            b"IDAT" => compressed.extend_from_slice(chunk_data),
+            b"IEND" => break,
+            // ancillary chunks, like gamma or transparency, are ignored
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or("PNG file has no IHDR chunk")?;
+    if header.color_type == 3 && palette.is_empty() {
+        return Err("palette PNG without a PLTE chunk".to_string());
+    }
+    let raw = zlib::decompress(&compressed)?;
+
+    let (width, height) = (header.width, header.height);
+    let mut pixels = vec![Vec3::new(0.0, 0.0, 0.0); width * height];
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced {
+        &ADAM7
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+
+    let mut remaining = &raw[..];
+    for &(x0, y0, dx, dy) in passes {
+        let pass_width = width.saturating_sub(x0).div_ceil(dx);
+        let pass_height = height.saturating_sub(y0).div_ceil(dy);
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let row_len = (pass_width * header.bits_per_pixel()).div_ceil(8);
+        let bytes_per_pixel = header.bits_per_pixel().div_ceil(8);
+        let mut previous = vec![0; row_len];
+        for pass_y in 0..pass_height {
+            if remaining.len() < row_len + 1 {
+                return Err("PNG image data is truncated".to_string());
+            }
+            let (filter_type, row) = (remaining[0], &remaining[1..row_len + 1]);
+            remaining = &remaining[row_len + 1..];
+            if filter_type > 4 {
+                return Err(format!("invalid PNG filter type {filter_type}"));
+            }
+
+            let mut current = row.to_vec();
+            for i in 0..row_len {
+                let a = if i >= bytes_per_pixel {
+                    current[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let c = if i >= bytes_per_pixel {
+                    previous[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                current[i] = current[i].wrapping_add(predict(filter_type, a, previous[i], c));
+            }
+
+            for pass_x in 0..pass_width {
+                let (x, y) = (x0 + pass_x * dx, y0 + pass_y * dy);
+                pixels[y * width + x] = header.pixel(&current, pass_x, &palette)?;
+            }
+            previous = current;
+        }
+    }
+
+    Ok(HdrImage::new(height, width, pixels))
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: usize,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Header, String> {
+        if data.len() != 13 {
+            return Err("invalid IHDR chunk".to_string());
+        }
+        let dimension = |i: usize| u32::from_be_bytes(data[i..i + 4].try_into().unwrap()) as usize;
+        let header = Header {
+            width: dimension(0),
+            height: dimension(4),
+            bit_depth: data[8] as usize,
+            color_type: data[9],
+            interlaced: data[12] == 1,
+        };
+
+        let valid_depths: &[usize] = match header.color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            other => return Err(format!("invalid PNG color type {other}")),
+        };
+        if !valid_depths.contains(&header.bit_depth) {
+            return Err(format!(
+                "invalid bit depth {} for PNG color type {}",
+                header.bit_depth, header.color_type
+            ));
+        }
+        if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+            return Err("unknown PNG compression, filter or interlace method".to_string());
+        }
+        check_size("PNG", header.width, header.height)?;
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth
+    }
+
+    /// Sample `index` of an unfiltered row, packed most significant bits first.
+    fn sample(&self, row: &[u8], index: usize) -> u16 {
+        match self.bit_depth {
+            16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+            8 => row[index] as u16,
+            depth => {
+                let bit = index * depth;
+                let shift = 8 - depth - bit % 8;
+                ((row[bit / 8] >> shift) & ((1 << depth) - 1) as u8) as u16
+            }
+        }
+    }
+
+    fn pixel(&self, row: &[u8], x: usize, palette: &[Vec3]) -> Result<Vec3, String> {
+        let first = x * self.channels();
+        if self.color_type == 3 {
+            let index = self.sample(row, first) as usize;
+            return palette
+                .get(index)
+                .copied()
+                .ok_or_else(|| format!("PNG palette index {index} out of range"));
+        }
+
+        let max = ((1u32 << self.bit_depth) - 1) as f64;
+        let value = |i: usize| self.sample(row, first + i) as f64 / max;
+        Ok(match self.color_type {
+            0 | 4 => Vec3::new(value(0), value(0), value(0)),
+            _ => Vec3::new(value(0), value(1), value(2)),
+        })
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(chunk_type);
+    out.extend(data);
+    let crc = !zlib::update_crc32(zlib::update_crc32(0xffff_ffff, chunk_type), data);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Appends the filter type and filtered bytes of one row, choosing the filter
+/// with the smallest sum of absolute differences like libpng does.
+fn filter_row(row: &[u8], previous: &[u8], bytes_per_pixel: usize, out: &mut Vec<u8>) {
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+
+    for filter_type in 0..5u8 {
+        let filtered: Vec<u8> = (0..row.len())
+            .map(|i| {
+                let a = if i >= bytes_per_pixel {
+                    row[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let b = previous[i];
+                let c = if i >= bytes_per_pixel {
+                    previous[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                row[i].wrapping_sub(predict(filter_type, a, b, c))
+            })
+            .collect();
+        let cost = filtered
+            .iter()
+            .map(|&v| (v as i8).unsigned_abs() as u64)
+            .sum();
+        if best
+            .as_ref()
+            .is_none_or(|(best_cost, _, _)| cost < *best_cost)
+        {
+            best = Some((cost, filter_type, filtered));
+        }
+    }
+
+    let (_, filter_type, filtered) = best.unwrap();
+    out.push(filter_type);
+    out.extend(filtered);
+}
+
+/// Value the filter predicts from the left (`a`), upper (`b`) and upper left (`c`) bytes.
+pub(super) fn predict(filter_type: u8, a: u8, b: u8, c: u8) -> u8 {
+    match filter_type {
+        0 => 0,
+        1 => a,
+        2 => b,
+        3 => ((a as u16 + b as u16) / 2) as u8,
+        _ => paeth(a, b, c),
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, PngColor};
+    use crate::image::zlib::crc32;
+
+    #[test]
+    fn test_encode_chunks() {
+        let samples: Vec<u16> = (0..2 * 3 * 4).map(|v| v * 1000).collect();
+        let png = encode(2, 3, PngColor::Rgba, 16, &samples);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        // IHDR: 13 bytes of header data followed by the CRC of type and data
+        assert_eq!(&png[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&png[16..29], &[0, 0, 0, 2, 0, 0, 0, 3, 16, 6, 0, 0, 0]);
+        assert_eq!(&png[29..33], &crc32(&png[12..29]).to_be_bytes());
+
+        assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+    }
+
+    #[test]
+    fn test_decode() {
+        let samples: Vec<u16> = (0..5 * 4 * 3).map(|v| v * 4).collect();
+        for bit_depth in [8, 16] {
+            let max = if bit_depth == 8 { 255.0 } else { 65535.0 };
+            let image = decode(&encode(5, 4, PngColor::Rgb, bit_depth, &samples)).unwrap();
+            assert_eq!((image.width(), image.height()), (5, 4));
+            let pixel = image.at(2, 3);
+            assert_eq!(pixel.x * max, samples[39] as f64);
+            assert_eq!(pixel.z * max, samples[41] as f64);
+        }
+
+        // a 3x2 interlaced 2 bit grayscale image. Only the passes starting at
+        // pixels (0, 0), (2, 0), (1, 0) and the second row aren't empty. The
+        // first row counts up from 1, the second is black.
+        let rows: Vec<u8> = vec![0, 0b0100_0000, 0, 0b1100_0000, 0, 0b1000_0000, 0, 0];
+        let mut png = super::SIGNATURE.to_vec();
+        super::write_chunk(&mut png, b"IHDR", &[0, 0, 0, 3, 0, 0, 0, 2, 2, 0, 0, 0, 1]);
+        super::write_chunk(&mut png, b"IDAT", &crate::image::zlib::compress(&rows));
+        super::write_chunk(&mut png, b"IEND", &[]);
+        let image = decode(&png).unwrap();
+        let gray: Vec<f64> = image.pixels().iter().map(|p| p.y * 3.0).collect();
+        assert_eq!(gray, vec![1.0, 2.0, 3.0, 0.0, 0.0, 0.0]);
+
+        let last = png.len() - 20;
+        png[last] ^= 1;
+        assert!(decode(&png).is_err());
+
+        // the size is checked before anything is allocated for the pixels
+        let mut huge = super::SIGNATURE.to_vec();
+        super::write_chunk(&mut huge, b"IHDR", &[0, 1, 0, 0, 0, 1, 0, 0, 8, 0, 0, 0, 0]);
+        assert_eq!(
+            decode(&huge).err().unwrap(),
+            "PNG image of 65536 x 65536 pixels is too large"
+        );
+    }
+}
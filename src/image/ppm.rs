@@ -0,0 +1,126 @@
+//! Portable pixmaps, both the ASCII (P3) and the binary (P6) variant.
+
+use super::{check_size, HdrImage};
+use crate::vec3::Vec3;
+
+/// Decodes a PPM into an image with samples scaled to `[0, 1]`, still gamma
+/// encoded.
+pub fn decode(data: &[u8]) -> Result<HdrImage, String> {
+    let mut reader = Reader { data, position: 0 };
+    let binary = match reader.token()? {
+        b"P3" => false,
+        b"P6" => true,
+        _ => return Err("not a P3 or P6 PPM file".to_string()),
+    };
+    let width = reader.number()? as usize;
+    let height = reader.number()? as usize;
+    let max_value = reader.number()?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(format!("invalid PPM maximum value {max_value}"));
+    }
+    check_size("PPM", width, height)?;
+
+    let sample_count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or("PPM image is too large")?;
+    let samples: Vec<u32> = if binary {
+        // exactly one whitespace character separates the header from the data
+        let start = reader.position + 1;
+        let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+        let bytes = data
+            .get(start..start + sample_count * bytes_per_sample)
+            .ok_or("PPM image data is truncated")?;
+        if bytes_per_sample == 1 {
+            bytes.iter().map(|&b| b as u32).collect()
+        } else {
+            bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+                .collect()
+        }
+    } else {
+        (0..sample_count)
+            .map(|_| reader.number())
+            .collect::<Result<_, _>>()?
+    };
+    if let Some(sample) = samples.iter().find(|&&s| s > max_value) {
+        return Err(format!(
+            "PPM sample {sample} exceeds the maximum value {max_value}"
+        ));
+    }
+
+    let scale = max_value as f64;
+    let pixels = samples
+        .chunks_exact(3)
+        .map(|rgb| Vec3::new(rgb[0] as f64, rgb[1] as f64, rgb[2] as f64) / scale)
+        .collect();
+    Ok(HdrImage::new(height, width, pixels))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    /// The next whitespace separated token, skipping `#` comments.
+    fn token(&mut self) -> Result<&[u8], String> {
+        loop {
+            match self.data.get(self.position) {
+                Some(b'#') => {
+                    while !matches!(self.data.get(self.position), Some(b'\n' | b'\r') | None) {
+                        self.position += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.position += 1,
+                Some(_) => break,
+                None => return Err("PPM file is truncated".to_string()),
+            }
+        }
+        let start = self.position;
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|c| !c.is_ascii_whitespace() && *c != b'#')
+        {
+            self.position += 1;
+        }
+        Ok(&self.data[start..self.position])
+    }
+
+    fn number(&mut self) -> Result<u32, String> {
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| {
+                format!(
+                    "invalid number '{}' in PPM file",
+                    String::from_utf8_lossy(token)
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    #[test]
+    fn test_decode() {
+        let ascii = decode(b"P3\n# a comment\n2 1\n255\n255 0 0  0 0 51\n").unwrap();
+        assert_eq!((ascii.width(), ascii.height()), (2, 1));
+        assert_eq!(ascii.at(0, 0).x, 1.0);
+        assert_eq!(ascii.at(0, 1).z, 0.2);
+
+        let mut binary = b"P6 1 2 65535\n".to_vec();
+        binary.extend([0xff, 0xff, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0, 0]);
+        let binary = decode(&binary).unwrap();
+        assert_eq!(binary.at(0, 0).x, 1.0);
+        assert_eq!(binary.at(1, 0).y, 32768.0 / 65535.0);
+
+        assert!(decode(b"P3 1 1 255 256 0 0").is_err());
+        assert!(decode(b"P6 2 2 255\n\0\0\0").is_err());
+    }
+}
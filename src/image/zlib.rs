@@ -0,0 +1,549 @@
+//! Just enough of zlib (RFC 1950) and deflate (RFC 1951) for the image formats.
+
+const WINDOW_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+// how many earlier occurrences of a prefix are tried before settling on a match
+const MAX_CHAIN: usize = 48;
+const MAX_STORED_BLOCK: usize = 65535;
+const MAX_CODE_LENGTH: usize = 15;
+// order in which a dynamic block lists the code lengths of its code length code
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Compresses `data` into a zlib stream using LZ77 with the fixed Huffman
+/// codes, falling back to stored blocks for data that doesn't compress.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let compressed = deflate_fixed(data);
+    if compressed.len() < data.len() + data.len() / MAX_STORED_BLOCK * 5 + 5 {
+        out.extend(compressed);
+    } else {
+        out.extend(deflate_stored(data));
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Wraps `data` in a zlib stream of uncompressed blocks.
+pub fn compress_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate_stored(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn deflate_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_STORED_BLOCK * 5 + 5);
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        // an empty stream still needs one final block
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let is_final = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(is_final as u8);
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(chunk);
+    }
+    out
+}
+
+fn deflate_fixed(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // a single final block with fixed Huffman codes
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    let mut matcher = Matcher::new(data);
+    let mut i = 0;
+    while i < data.len() {
+        let (length, distance) = matcher.longest_match(i);
+        if length >= MIN_MATCH {
+            write_length(&mut writer, length);
+            write_distance(&mut writer, distance);
+            for j in i..i + length {
+                matcher.insert(j);
+            }
+            i += length;
+        } else {
+            write_literal(&mut writer, data[i] as u16);
+            matcher.insert(i);
+            i += 1;
+        }
+    }
+
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+/// Hash chains over the three byte prefixes seen in the last window.
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl Matcher<'_> {
+    fn new(data: &[u8]) -> Matcher<'_> {
+        Matcher {
+            data,
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            previous: vec![usize::MAX; WINDOW_SIZE],
+        }
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        let data = self.data;
+        let v = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+        (v.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, i: usize) {
+        if i + MIN_MATCH <= self.data.len() {
+            let h = self.hash(i);
+            self.previous[i % WINDOW_SIZE] = self.head[h];
+            self.head[h] = i;
+        }
+    }
+
+    fn longest_match(&self, position: usize) -> (usize, usize) {
+        let data = self.data;
+        if position + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let max_length = MAX_MATCH.min(data.len() - position);
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(position)];
+
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || position - candidate > WINDOW_SIZE - 1 {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, position - candidate);
+                if length == max_length {
+                    break;
+                }
+            }
+            let next = self.previous[candidate % WINDOW_SIZE];
+            // stale entries from an earlier trip around the window point forwards
+            if next == usize::MAX || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        best
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let (code, length) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    writer.write_code(code as u32, length);
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(writer, 257 + index as u16);
+    writer.write_bits(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index] as u32,
+    );
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    // distance codes are all five bits long in the fixed code
+    writer.write_code(index as u32, 5);
+    writer.write_bits(
+        (distance - DISTANCE_BASE[index] as usize) as u32,
+        DISTANCE_EXTRA[index] as u32,
+    );
+}
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    /// Writes `count` bits of `value`, least significant bit first.
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed starting with their most significant bit.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// Inflates a zlib stream, checking its header and checksum.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6 {
+        return Err("zlib stream is truncated".to_string());
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err("invalid zlib header".to_string());
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string());
+    }
+
+    let mut reader = BitReader::new(&data[2..]);
+    let out = inflate(&mut reader)?;
+    let checksum_start = 2 + reader.byte_position();
+    let checksum = data
+        .get(checksum_start..checksum_start + 4)
+        .ok_or("zlib stream is missing its checksum")?;
+    if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(&out) {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+fn inflate(reader: &mut BitReader) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    loop {
+        let is_final = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let len = reader.bits(16)?;
+                if reader.bits(16)? != !len & 0xffff {
+                    return Err("corrupt stored block length".to_string());
+                }
+                for _ in 0..len {
+                    out.push(reader.bits(8)? as u8);
+                }
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(reader, &literals, &distances, &mut out)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(reader)?;
+                inflate_block(reader, &literals, &distances, &mut out)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if is_final {
+            return Ok(out);
+        }
+    }
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    literals: &Huffman,
+    distances: &Huffman,
+    out: &mut Vec<u8>,
+) -> Result<(), String> {
+    loop {
+        let symbol = reader.decode(literals)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = reader.decode(distances)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("invalid deflate distance code".to_string());
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("deflate distance reaches before the start".to_string());
+                }
+                // byte by byte, since a match may overlap the bytes it produces
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+            _ => return Err("invalid deflate length code".to_string()),
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (
+        Huffman::new(&lengths).unwrap(),
+        Huffman::new(&[5; 30]).unwrap(),
+    )
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_length_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_length_lengths[symbol] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_length_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match reader.decode(&code_lengths)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or("deflate code length repeat without a previous length")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err("deflate code lengths overrun".to_string());
+    }
+    if lengths[256] == 0 {
+        return Err("deflate block has no end of block code".to_string());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+/// A canonical Huffman code, decoded one bit at a time as in zlib's puff.
+struct Huffman {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        // codes may be incomplete, which happens for a single distance code,
+        // but never over-subscribed
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return Err("over-subscribed deflate code".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[MAX_CODE_LENGTH + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    bit_count: u32,
+}
+
+impl BitReader<'_> {
+    fn new(data: &[u8]) -> BitReader<'_> {
+        BitReader {
+            data,
+            position: 0,
+            buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    /// Reads `count` (at most 16) bits, least significant bit first.
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.bit_count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or("deflate stream is truncated")?;
+            self.buffer |= (byte as u32) << self.bit_count;
+            self.position += 1;
+            self.bit_count += 8;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &huffman.counts[1..] {
+            code |= self.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid deflate code".to_string())
+    }
+
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.bit_count = 0;
+    }
+
+    /// Position of the first byte that hasn't been consumed.
+    fn byte_position(&self) -> usize {
+        self.position - (self.bit_count / 8) as usize
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be summed before b could overflow
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    !update_crc32(0xffff_ffff, data)
+}
+
+/// Feeds more data into a running CRC, for checksums over several buffers.
+pub fn update_crc32(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        let mut c = crc ^ byte as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        c
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, compress, compress_stored, crc32, decompress};
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_compress() {
+        let data: Vec<u8> = (0..10000).map(|i| (i % 7 * 31) as u8).collect();
+        let compressed = compress(&data);
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(
+            compress_stored(&[]),
+            vec![0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_decompress() {
+        let data: Vec<u8> = (0..100_000u64).map(|i| (i * i % 251 / 3) as u8).collect();
+        assert_eq!(decompress(&compress(&data)).unwrap(), data);
+        assert_eq!(decompress(&compress_stored(&data)).unwrap(), data);
+
+        // from Python's zlib.compress at level 9, which picks a dynamic block
+        let hex = "78da1d88c11100300c406625f69fa1691eee2003f2590926dbd6fba9e1f400398c0f51";
+        let stream: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(
+            decompress(&stream).unwrap(),
+            b"bacaabaaabacaadaacdbdbaabbcaabadbbbdabcd"
+        );
+
+        let mut corrupt = compress(&data);
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        assert!(decompress(&corrupt).is_err());
+    }
+}
@@ -0,0 +1,752 @@
+use crate::{
+    background::Background,
+    bad_rand,
+    hittable::{HitRecord, Hittable},
+    light::Light,
+    ray::Ray,
+    sampling::power_heuristic,
+    scene::Scene,
+    vec3::Vec3,
+    RenderSettings,
+};
+
+/// A way of computing the light that reaches the camera. `render` asks it
+/// once for every camera ray.
+pub trait Integrator: Send + Sync {
+    /// Returns the linear radiance arriving along `r`. Values are unbounded
+    /// and only get quantized once the samples of a pixel have been averaged.
+    fn radiance(&self, r: &Ray, scene: &Scene) -> Vec3;
+}
+
+/// Which integrator to render with. Built into one by `build` once the rest
+/// of the render settings are known.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum IntegratorKind {
+    /// `PathTracer`
+    #[default]
+    Path,
+    /// `RecursivePathTracer`
+    RecursivePath,
+    /// `WhittedRayTracer`
+    Whitted,
+    /// `AmbientOcclusion`, with the distance up to which objects occlude.
+    AmbientOcclusion { distance: f64 },
+    /// `AovIntegrator`
+    Aov(Aov),
+}
+
+impl IntegratorKind {
+    /// Names accepted by `from_name`, as shown in the usage.
+    pub const NAMES: &'static [&'static str] = &[
+        "path",
+        "recursive_path",
+        "whitted",
+        "ambient_occlusion",
+        "normal",
+        "front_face",
+        "depth",
+        "albedo",
+        "uv",
+        "object_id",
+    ];
+
+    /// Looks up an integrator by name. Ambient occlusion considers objects
+    /// at any distance.
+    pub fn from_name(name: &str) -> Option<IntegratorKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "path" => Some(IntegratorKind::Path),
+            "recursive_path" => Some(IntegratorKind::RecursivePath),
+            "whitted" => Some(IntegratorKind::Whitted),
+            "ambient_occlusion" => Some(IntegratorKind::AmbientOcclusion {
+                distance: f64::INFINITY,
+            }),
+            _ => Aov::from_name(name).map(IntegratorKind::Aov),
+        }
+    }
+
+    pub fn build(&self, settings: &RenderSettings) -> Box<dyn Integrator> {
+        match *self {
+            IntegratorKind::Path => Box::new(PathTracer {
+                max_depth: settings.max_depth,
+                roulette_depth: settings.roulette_depth,
+            }),
+            IntegratorKind::RecursivePath => Box::new(RecursivePathTracer {
+                max_depth: settings.max_depth,
+            }),
+            IntegratorKind::Whitted => Box::new(WhittedRayTracer {
+                max_depth: settings.max_depth,
+            }),
+            IntegratorKind::AmbientOcclusion { distance } => {
+                Box::new(AmbientOcclusion { distance })
+            }
+            IntegratorKind::Aov(aov) => Box::new(AovIntegrator { aov }),
+        }
+    }
+}
+
+/// Something about the first surface a camera ray hits, rendered as an image
+/// of its own to see what the renderer sees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    /// Shading normal, facing the ray and mapped from `[-1, 1]` to `[0, 1]`.
+    Normal,
+    /// Green where the ray hit the outside of a surface, red for the inside.
+    FrontFace,
+    /// Distance along the ray, which needs an HDR or OpenEXR output to be
+    /// of any use.
+    Depth,
+    /// `Material::albedo`
+    Albedo,
+    /// Surface coordinates in red and green.
+    Uv,
+    /// A color of its own for every object of the scene.
+    ObjectId,
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Aov> {
+        match name.to_ascii_lowercase().as_str() {
+            "normal" => Some(Aov::Normal),
+            "front_face" => Some(Aov::FrontFace),
+            "depth" => Some(Aov::Depth),
+            "albedo" => Some(Aov::Albedo),
+            "uv" => Some(Aov::Uv),
+            "object_id" => Some(Aov::ObjectId),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::FrontFace => "front_face",
+            Aov::Depth => "depth",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::ObjectId => "object_id",
+        }
+    }
+
+    /// Whether the values of the pass stay in `[0, 1]`, so that PNG and PPM
+    /// files can hold them.
+    pub fn fits_unit_range(&self) -> bool {
+        !matches!(self, Aov::Depth)
+    }
+}
+
+/// Follows paths iteratively and samples one of the lights of the scene at
+/// every diffuse bounce.
+///
+/// Emission that a scattered ray finds is combined with the light samples of
+/// the previous bounce through multiple importance sampling. Paths that have
+/// bounced `roulette_depth` times play Russian roulette.
+pub struct PathTracer {
+    pub max_depth: usize,
+    pub roulette_depth: usize,
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+        // density of the last bounce, None for camera rays and mirror-like bounces
+        let mut scattering_pdf: Option<f64> = None;
+
+        for depth in 0..self.max_depth {
+            // `distance` is how far the ray went to find the emission
+            let emission_weight = |ray: &Ray, distance: f64| match scattering_pdf {
+                Some(pdf) => power_heuristic(pdf, light_pdf(scene, ray, distance)),
+                None => 1.0,
+            };
+
+            let rec = match scene.world.hit(&ray, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    let background = scene.background.value(ray.direction());
+                    radiance = radiance
+                        + throughput.component_mul(&background)
+                            * emission_weight(&ray, f64::INFINITY);
+                    break;
+                }
+            };
+
+            let material = rec.material();
+            if material.is_emissive() {
+                let (u, v) = rec.uv();
+                let emitted = material.emitted(u, v, rec.point());
+                radiance = radiance
+                    + throughput.component_mul(&emitted) * emission_weight(&ray, rec.t_value());
+            }
+
+            let (attenuation, new_ray) = match material.scatter(&ray, rec.clone()) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+            let pdf = material.pdf(&ray, &rec, new_ray.direction());
+            // only paths with room for one more vertex can reach a light
+            if pdf > 0.0 && depth + 1 < self.max_depth && !scene.lights.is_empty() {
+                radiance = radiance + throughput.component_mul(&sample_light(&ray, &rec, scene));
+            }
+
+            scattering_pdf = (pdf > 0.0).then_some(pdf);
+            throughput = throughput.component_mul(&attenuation);
+            ray = new_ray;
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.0);
+                if bad_rand::rand_f64() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+
+        radiance
+    }
+}
+
+/// The random walk the renderer started out with: every bounce recurses into
+/// the ray the material scatters.
+///
+/// At diffuse surfaces the background is also sampled directly, if it supports
+/// that, and combined with the scattered ray through multiple importance
+/// sampling. Other lights are only found by chance.
+pub struct RecursivePathTracer {
+    pub max_depth: usize,
+}
+
+impl Integrator for RecursivePathTracer {
+    fn radiance(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        trace_ray(
+            r,
+            scene.world.as_ref(),
+            scene.background.as_ref(),
+            self.max_depth,
+            None,
+        )
+    }
+}
+
+/// `scattering_pdf` is the density with which `r` was scattered towards the
+/// background, `None` for camera rays and mirror-like bounces.
+fn trace_ray(
+    r: &Ray,
+    world: &dyn Hittable,
+    background: &dyn Background,
+    max_depth: usize,
+    scattering_pdf: Option<f64>,
+) -> Vec3 {
+    if max_depth == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    let rec = world.hit(r, 0.001, f64::INFINITY);
+
+    if let Some(final_rec) = rec {
+        let (u, v) = final_rec.uv();
+        let material = final_rec.material();
+        let emitted = material.emitted(u, v, final_rec.point());
+        if let Some((attenuation, new_ray)) = material.scatter(r, final_rec.clone()) {
+            let pdf = material.pdf(r, &final_rec, new_ray.direction());
+            // paths that would end on the background at the next bounce
+            // anyway can't be lengthened by sampling it here
+            let direct = if pdf > 0.0 && max_depth > 1 {
+                sample_background(r, &final_rec, world, background)
+            } else {
+                Vec3::new(0.0, 0.0, 0.0)
+            };
+            let color = trace_ray(
+                &new_ray,
+                world,
+                background,
+                max_depth - 1,
+                (pdf > 0.0).then_some(pdf),
+            );
+            return emitted + direct + attenuation.component_mul(&color);
+        }
+
+        return emitted;
+    }
+
+    let radiance = background.value(r.direction());
+    match scattering_pdf {
+        Some(pdf) => radiance * power_heuristic(pdf, background.pdf(r.direction())),
+        None => radiance,
+    }
+}
+
+/// Radiance reaching the hit in `rec` from a direction towards the background
+/// picked by the background itself, weighted against the chance of `scatter`
+/// finding it.
+fn sample_background(
+    r: &Ray,
+    rec: &HitRecord,
+    world: &dyn Hittable,
+    background: &dyn Background,
+) -> Vec3 {
+    let black = Vec3::new(0.0, 0.0, 0.0);
+    let (direction, light_pdf) = match background.sample() {
+        Some(sample) => sample,
+        None => return black,
+    };
+    let material = rec.material();
+    let scattering_pdf = material.pdf(r, rec, &direction);
+    if scattering_pdf <= 0.0 {
+        return black;
+    }
+    let transmittance = world.transmittance(
+        &Ray::with_time(rec.point(), &direction, r.time()),
+        0.001,
+        f64::INFINITY,
+    );
+    if transmittance <= 0.0 {
+        return black;
+    }
+
+    let weight = power_heuristic(light_pdf, scattering_pdf);
+    material
+        .eval(r, rec, &direction)
+        .component_mul(&background.value(&direction))
+        * (transmittance * weight / light_pdf)
+}
+
+/// Classic ray tracing: mirrors and glass are followed, everything else is
+/// shaded with a shadow ray towards every light and nothing more. Scenes
+/// without lights are shaded with the background seen along the normal
+/// instead, without shadows.
+pub struct WhittedRayTracer {
+    pub max_depth: usize,
+}
+
+impl WhittedRayTracer {
+    fn trace(&self, r: &Ray, scene: &Scene, max_depth: usize) -> Vec3 {
+        if max_depth == 0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+        let rec = match scene.world.hit(r, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return scene.background.value(r.direction()),
+        };
+
+        let material = rec.material();
+        let (u, v) = rec.uv();
+        let emitted = material.emitted(u, v, rec.point());
+        let (attenuation, new_ray) = match material.scatter(r, rec.clone()) {
+            Some(scattered) => scattered,
+            None => return emitted,
+        };
+
+        if material.pdf(r, &rec, new_ray.direction()) <= 0.0 {
+            let color = self.trace(&new_ray, scene, max_depth - 1);
+            return emitted + attenuation.component_mul(&color);
+        }
+        if scene.lights.is_empty() {
+            return emitted + attenuation.component_mul(&scene.background.value(rec.normal()));
+        }
+        scene
+            .lights
+            .iter()
+            .filter_map(|light| sample_direct(r, &rec, scene, light.as_ref()))
+            .fold(emitted, |acc, (reflected, light_pdf, _)| {
+                acc + reflected / light_pdf
+            })
+    }
+}
+
+impl Integrator for WhittedRayTracer {
+    fn radiance(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        self.trace(r, scene, self.max_depth)
+    }
+}
+
+/// Shows how much of the hemisphere above the first hit is open, white for
+/// none of it being blocked by objects closer than `distance`.
+pub struct AmbientOcclusion {
+    pub distance: f64,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let rec = match scene.world.hit(r, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return black,
+        };
+        // cosine weighted, so the fraction of open rays is the answer
+        let mut direction = *rec.normal() + Vec3::random_unit_vector();
+        if direction.is_near_zero() {
+            direction = *rec.normal();
+        }
+        match scene.world.hit(
+            &Ray::with_time(rec.point(), &direction, r.time()),
+            0.001,
+            self.distance,
+        ) {
+            Some(_) => black,
+            None => Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Renders one `Aov` instead of light. Rays that miss everything are black.
+pub struct AovIntegrator {
+    pub aov: Aov,
+}
+
+impl Integrator for AovIntegrator {
+    fn radiance(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        let rec = match scene.world.hit(r, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return Vec3::new(0.0, 0.0, 0.0),
+        };
+        match self.aov {
+            Aov::Normal => (*rec.normal() + 1.0) * 0.5,
+            Aov::FrontFace if rec.front_face() => Vec3::new(0.0, 1.0, 0.0),
+            Aov::FrontFace => Vec3::new(1.0, 0.0, 0.0),
+            Aov::Depth => Vec3::new(rec.t_value(), rec.t_value(), rec.t_value()),
+            Aov::Albedo => rec.material().albedo(&rec),
+            Aov::Uv => {
+                let (u, v) = rec.uv();
+                Vec3::new(u, v, 0.0)
+            }
+            Aov::ObjectId => id_color(rec.object_id()),
+        }
+    }
+}
+
+/// A bright color that tells neighbouring ids apart.
+fn id_color(id: usize) -> Vec3 {
+    // splitmix64 finalizer, to scatter consecutive ids over the colors
+    let mut z = (id as u64).wrapping_mul(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^= z >> 31;
+    let channel = |shift: u32| 0.2 + 0.8 * ((z >> shift) & 0xFF) as f64 / 255.0;
+    Vec3::new(channel(0), channel(8), channel(16))
+}
+
+/// Radiance reaching the hit in `rec` from one light of the scene, picked
+/// uniformly, weighted against the chance of `scatter` finding it.
+fn sample_light(r: &Ray, rec: &HitRecord, scene: &Scene) -> Vec3 {
+    let light = &scene.lights[bad_rand::rand_usize(0..scene.lights.len())];
+    match sample_direct(r, rec, scene, light.as_ref()) {
+        Some((reflected, light_pdf, scattering_pdf)) => {
+            let light_pdf = light_pdf / scene.lights.len() as f64;
+            reflected * (power_heuristic(light_pdf, scattering_pdf) / light_pdf)
+        }
+        None => Vec3::new(0.0, 0.0, 0.0),
+    }
+}
+
+/// Samples `light` from the hit in `rec` and returns the radiance reflected
+/// back along `r`, not yet divided by the density of the sample, along with
+/// that density and the one of `scatter` picking the same direction. `None`
+/// if the light is blocked or the material doesn't reflect it. Media in the
+/// way let only part of the light through.
+fn sample_direct(
+    r: &Ray,
+    rec: &HitRecord,
+    scene: &Scene,
+    light: &dyn Light,
+) -> Option<(Vec3, f64, f64)> {
+    let sample = light
+        .sample(rec.point())
+        .filter(|sample| sample.pdf > 0.0)?;
+    let material = rec.material();
+    let scattering_pdf = material.pdf(r, rec, &sample.direction);
+    if scattering_pdf <= 0.0 {
+        return None;
+    }
+    let shadow_ray = Ray::with_time(rec.point(), &sample.direction, r.time());
+    let transmittance = scene
+        .world
+        .transmittance(&shadow_ray, 0.001, sample.distance - 0.001);
+    if transmittance <= 0.0 {
+        return None;
+    }
+
+    let reflected = material
+        .eval(r, rec, &sample.direction)
+        .component_mul(&sample.radiance)
+        * transmittance;
+    Some((reflected, sample.pdf, scattering_pdf))
+}
+
+/// Density with which `sample_light` picks the direction of `r` from its
+/// origin and ends up on the emitter `r` found `distance` away, infinitely far
+/// for the background.
+///
+/// Only lights at that distance can be what the ray found. Emitters that
+/// aren't lights, like planes or objects that move, are never sampled and get
+/// a density of zero.
+fn light_pdf(scene: &Scene, r: &Ray, distance: f64) -> f64 {
+    if scene.lights.is_empty() {
+        return 0.0;
+    }
+    let found = |light_distance: f64| {
+        light_distance == distance
+            || (distance.is_finite() && (light_distance - distance).abs() <= 1e-6 * distance)
+    };
+    scene
+        .lights
+        .iter()
+        .filter(|light| light.distance(r.origin(), r.direction()).is_some_and(found))
+        .map(|light| light.pdf(r.origin(), r.direction()))
+        .sum::<f64>()
+        / scene.lights.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        id_color, AmbientOcclusion, Aov, AovIntegrator, Integrator, PathTracer, WhittedRayTracer,
+    };
+    use crate::{
+        background::{EnvironmentMap, SolidBackground},
+        bad_rand,
+        camera::CameraSettings,
+        hittable::{identified::Identified, sphere::Sphere, HitRecord, Hittable},
+        image::HdrImage,
+        light::SphereLight,
+        material::{DiffuseLight, Lambertian, Material},
+        ray::Ray,
+        scene::Scene,
+        vec3::Vec3,
+        RenderSettings,
+    };
+    use std::sync::Arc;
+
+    /// A small lamp straight above a large diffuse floor.
+    fn lamp_scene() -> Scene {
+        let lamp = Arc::new(DiffuseLight::new(&Vec3::new(10.0, 10.0, 10.0)));
+        let center = Vec3::new(0.0, 3.0, 0.0);
+        let world: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Sphere::new(
+                &Vec3::new(0.0, -1e6, 0.0),
+                1e6,
+                Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5))),
+            )),
+            Box::new(Sphere::new(&center, 0.5, lamp.clone())),
+        ];
+        Scene {
+            camera: CameraSettings::default(),
+            settings: RenderSettings::new(1, 1, 1, 2),
+            world: Box::new(world),
+            background: Arc::new(SolidBackground::new(&Vec3::new(0.0, 0.0, 0.0))),
+            lights: vec![Arc::new(SphereLight::new(&center, 0.5, lamp))],
+        }
+    }
+
+    fn mean_radiance(integrator: &dyn Integrator, scene: &Scene, ray: &Ray) -> f64 {
+        let samples = 20000;
+        (0..samples)
+            .map(|_| integrator.radiance(ray, scene).x)
+            .sum::<f64>()
+            / samples as f64
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        bad_rand::seed(7);
+        let scene = lamp_scene();
+        let ray = Ray::new(&Vec3::new(1.0, 1.0, 0.0), &Vec3::new(-1.0, -1.0, 0.0));
+        // albedo / pi * radiance * pi * sin^2 of the half angle of the lamp
+        let expected = 0.5 * 10.0 * 0.25 / 9.0;
+
+        let path_tracer = PathTracer {
+            max_depth: 2,
+            roulette_depth: 2,
+        };
+        let mean = mean_radiance(&path_tracer, &scene, &ray);
+        assert!(
+            (mean - expected).abs() < 0.01 * expected,
+            "{mean} != {expected}"
+        );
+
+        let mean = mean_radiance(&WhittedRayTracer { max_depth: 2 }, &scene, &ray);
+        assert!(
+            (mean - expected).abs() < 0.01 * expected,
+            "{mean} != {expected}"
+        );
+    }
+
+    #[test]
+    fn test_emission_weights() {
+        bad_rand::seed(9);
+        let ray = Ray::new(&Vec3::new(1.0, 1.0, 0.0), &Vec3::new(-1.0, -1.0, 0.0));
+        let path_tracer = PathTracer {
+            max_depth: 2,
+            roulette_depth: 2,
+        };
+        let check = |scene: &Scene, expected: f64| {
+            let mean = mean_radiance(&path_tracer, scene, &ray);
+            assert!(
+                (mean - expected).abs() < 0.02 * expected,
+                "{mean} != {expected}"
+            );
+        };
+        let black_sky = || {
+            Arc::new(EnvironmentMap::new(
+                HdrImage::new(1, 1, vec![Vec3::new(0.0, 0.0, 0.0)]),
+                0.0,
+                1.0,
+            ))
+        };
+        let lamp = Arc::new(DiffuseLight::new(&Vec3::new(10.0, 10.0, 10.0)));
+
+        // a black sky that is sampled as well, and a second lamp straight
+        // behind the first, hidden by it, don't change what the floor gets
+        let expected = 0.5 * 10.0 * 0.25 / 9.0;
+        let mut with_sky = lamp_scene();
+        let sky = black_sky();
+        with_sky.background = sky.clone();
+        with_sky.lights.push(sky);
+        check(&with_sky, expected);
+        let mut with_hidden_lamp = lamp_scene();
+        with_hidden_lamp.lights.push(Arc::new(SphereLight::new(
+            &Vec3::new(0.0, 6.0, 0.0),
+            0.5,
+            lamp.clone(),
+        )));
+        check(&with_hidden_lamp, expected);
+
+        // a large lamp that isn't one of the lights is only found by
+        // scattered rays, which have to count fully whatever else is sampled
+        let mut unlisted = lamp_scene();
+        let world: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Sphere::new(
+                &Vec3::new(0.0, -1e6, 0.0),
+                1e6,
+                Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5))),
+            )),
+            Box::new(Sphere::new(&Vec3::new(0.0, 3.0, 0.0), 2.0, lamp)),
+        ];
+        unlisted.world = Box::new(world);
+        unlisted.lights = vec![black_sky()];
+        check(&unlisted, 0.5 * 10.0 * 4.0 / 9.0);
+
+        // only a light at the distance the emission was found counts
+        let scene = lamp_scene();
+        let up = Ray::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 1.0, 0.0));
+        assert!(super::light_pdf(&scene, &up, 2.5) > 0.0);
+        assert_eq!(super::light_pdf(&scene, &up, 1.0), 0.0);
+        assert_eq!(super::light_pdf(&scene, &up, f64::INFINITY), 0.0);
+    }
+
+    /// Reflects like `Lambertian` and glows on top of that.
+    struct GlowingLambertian(Lambertian);
+
+    impl Material for GlowingLambertian {
+        fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
+            self.0.scatter(r, hit_record)
+        }
+
+        fn emitted(&self, _u: f64, _v: f64, _point: &Vec3) -> Vec3 {
+            Vec3::new(1.0, 1.0, 1.0)
+        }
+
+        fn is_emissive(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_russian_roulette() {
+        bad_rand::seed(11);
+        // inside a closed sphere every bounce adds the emission once more,
+        // so the radiance is 1 / (1 - albedo) everywhere
+        let albedo = 0.8;
+        let material = GlowingLambertian(Lambertian::new(&Vec3::new(albedo, albedo, albedo)));
+        let scene = Scene {
+            camera: CameraSettings::default(),
+            settings: RenderSettings::new(1, 1, 1, 2),
+            world: Box::new(Sphere::new(
+                &Vec3::new(0.0, 0.0, 0.0),
+                1.0,
+                Arc::new(material),
+            )),
+            background: Arc::new(SolidBackground::new(&Vec3::new(0.0, 0.0, 0.0))),
+            lights: Vec::new(),
+        };
+
+        let ray = Ray::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 0.0, 1.0));
+        let path_tracer = PathTracer {
+            max_depth: 1000,
+            roulette_depth: 0,
+        };
+        let mean = mean_radiance(&path_tracer, &scene, &ray);
+        let expected = 1.0 / (1.0 - albedo);
+        assert!(
+            (mean - expected).abs() < 0.03 * expected,
+            "{mean} != {expected}"
+        );
+    }
+
+    #[test]
+    fn test_ambient_occlusion() {
+        bad_rand::seed(13);
+        let scene = lamp_scene();
+        let ray = Ray::new(&Vec3::new(0.0, 1.0, 0.0), &Vec3::new(0.0, -1.0, 0.0));
+        // the lamp covers a cosine weighted fraction of sin^2 of its half angle
+        let expected = 1.0 - 0.25 / 9.0;
+        let mean = mean_radiance(
+            &AmbientOcclusion {
+                distance: f64::INFINITY,
+            },
+            &scene,
+            &ray,
+        );
+        assert!((mean - expected).abs() < 0.005, "{mean} != {expected}");
+
+        let ambient_occlusion = AmbientOcclusion { distance: 2.0 };
+        assert_eq!(mean_radiance(&ambient_occlusion, &scene, &ray), 1.0);
+    }
+
+    #[test]
+    fn test_aovs() {
+        let albedo = Vec3::new(0.2, 0.4, 0.6);
+        let sphere = Sphere::new(
+            &Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(&albedo)),
+        );
+        let scene = Scene {
+            camera: CameraSettings::default(),
+            settings: RenderSettings::new(1, 1, 1, 1),
+            world: Box::new(Identified::new(7, Box::new(sphere))),
+            background: Arc::new(SolidBackground::new(&Vec3::new(1.0, 1.0, 1.0))),
+            lights: Vec::new(),
+        };
+        let aov = |aov: Aov, ray: &Ray| AovIntegrator { aov }.radiance(ray, &scene);
+
+        let outside = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let normal = aov(Aov::Normal, &outside);
+        assert_eq!((normal.x, normal.y, normal.z), (0.5, 0.5, 1.0));
+        assert_eq!(aov(Aov::Depth, &outside).x, 4.0);
+        assert_eq!(aov(Aov::FrontFace, &outside).y, 1.0);
+        assert_eq!(aov(Aov::Albedo, &outside).z, 0.6);
+        assert_eq!(aov(Aov::ObjectId, &outside).x, id_color(7).x);
+        assert!(id_color(7).x != id_color(8).x || id_color(7).y != id_color(8).y);
+
+        let inside = Ray::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 1.0, 0.0));
+        let front_face = aov(Aov::FrontFace, &inside);
+        assert_eq!((front_face.x, front_face.y), (1.0, 0.0));
+        let uv = aov(Aov::Uv, &inside);
+        assert!((uv.y - 1.0).abs() < 1e-9);
+
+        let miss = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(aov(Aov::Depth, &miss).x, 0.0);
+
+        // pixels of the id pass show one object each, even at the edges
+        let mut scene = scene;
+        scene.settings = RenderSettings::new(8, 8, 16, 1);
+        let ids = crate::render_aov(&scene, Aov::ObjectId);
+        assert!(ids
+            .pixels()
+            .iter()
+            .all(|pixel| pixel.x == 0.0 || pixel.x == id_color(7).x));
+        assert!(ids.pixels().iter().any(|pixel| pixel.x == 0.0));
+        assert!(ids.pixels().iter().any(|pixel| pixel.x != 0.0));
+    }
+}
@@ -0,0 +1,397 @@
+use std::fmt;
+
+/// A parsed JSON value along with the line and column (both starting at 1)
+/// where it begins in the source, so that errors found while interpreting the
+/// document can point back at it.
+#[derive(Debug)]
+pub struct Json {
+    pub value: JsonValue,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members in the order they appear in the source.
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug)]
+pub struct JsonError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+pub fn parse(source: &str) -> Result<Json, JsonError> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        index: 0,
+        line: 1,
+        column: 1,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected data after the document"));
+    }
+    Ok(value)
+}
+
+impl Json {
+    pub fn error(&self, message: impl Into<String>) -> JsonError {
+        JsonError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self.value {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "a boolean",
+            JsonValue::Number(_) => "a number",
+            JsonValue::String(_) => "a string",
+            JsonValue::Array(_) => "an array",
+            JsonValue::Object(_) => "an object",
+        }
+    }
+
+    /// Looks up a member of an object. Returns `None` for other values.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match &self.value {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<f64, JsonError> {
+        match self.value {
+            JsonValue::Number(n) => Ok(n),
+            _ => Err(self.error(format!("expected a number but found {}", self.kind()))),
+        }
+    }
+
+    pub fn as_usize(&self) -> Result<usize, JsonError> {
+        let n = self.as_f64()?;
+        if n < 0.0 || n.fract() != 0.0 || n > usize::MAX as f64 {
+            return Err(self.error(format!("expected a non-negative integer but found {n}")));
+        }
+        Ok(n as usize)
+    }
+
+    pub fn as_bool(&self) -> Result<bool, JsonError> {
+        match self.value {
+            JsonValue::Bool(b) => Ok(b),
+            _ => Err(self.error(format!("expected a boolean but found {}", self.kind()))),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, JsonError> {
+        match &self.value {
+            JsonValue::String(s) => Ok(s),
+            _ => Err(self.error(format!("expected a string but found {}", self.kind()))),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&[Json], JsonError> {
+        match &self.value {
+            JsonValue::Array(items) => Ok(items),
+            _ => Err(self.error(format!("expected an array but found {}", self.kind()))),
+        }
+    }
+
+    pub fn as_object(&self) -> Result<&[(String, Json)], JsonError> {
+        match &self.value {
+            JsonValue::Object(members) => Ok(members),
+            _ => Err(self.error(format!("expected an object but found {}", self.kind()))),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        JsonError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{expected}' but found '{c}'"))),
+            None => Err(self.error(format!("expected '{expected}' but the input ended"))),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, JsonError> {
+        let (line, column) = (self.line, self.column);
+        let value = match self.peek() {
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('"') => JsonValue::String(self.parse_string()?),
+            Some('-' | '0'..='9') => JsonValue::Number(self.parse_number()?),
+            Some('t') => self.parse_literal("true", JsonValue::Bool(true))?,
+            Some('f') => self.parse_literal("false", JsonValue::Bool(false))?,
+            Some('n') => self.parse_literal("null", JsonValue::Null)?,
+            Some(c) => return Err(self.error(format!("unexpected character '{c}'"))),
+            None => return Err(self.error("expected a value but the input ended")),
+        };
+        Ok(Json {
+            value,
+            line,
+            column,
+        })
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, JsonError> {
+        for expected in literal.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error(format!("invalid literal, expected '{literal}'")));
+            }
+            self.next();
+        }
+        Ok(value)
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('{')?;
+        let mut members: Vec<(String, Json)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a member name"));
+            }
+            let (line, column) = (self.line, self.column);
+            let name = self.parse_string()?;
+            if members.iter().any(|(existing, _)| *existing == name) {
+                return Err(JsonError {
+                    line,
+                    column,
+                    message: format!("duplicate member '{name}'"),
+                });
+            }
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            members.push((name, self.parse_value()?));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(JsonValue::Object(members)),
+                _ => return Err(self.error("expected ',' or '}' after an object member")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, JsonError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(JsonValue::Array(items));
+        }
+
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(JsonValue::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' after an array item")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => s.push(self.parse_unicode_escape()?),
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string"))
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid unicode escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let code = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&code) {
+            // high surrogate, must be followed by an escaped low surrogate
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            code
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn parse_number(&mut self) -> Result<f64, JsonError> {
+        let (line, column) = (self.line, self.column);
+        let start = self.index;
+
+        if self.peek() == Some('-') {
+            self.next();
+        }
+        match self.peek() {
+            Some('0') => {
+                self.next();
+            }
+            Some('1'..='9') => self.skip_digits(),
+            _ => return Err(self.error("expected a digit")),
+        }
+        if self.peek() == Some('.') {
+            self.next();
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit after the decimal point"));
+            }
+            self.skip_digits();
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.next();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.next();
+            }
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("expected a digit in the exponent"));
+            }
+            self.skip_digits();
+        }
+
+        let text: String = self.chars[start..self.index].iter().collect();
+        text.parse().map_err(|_| JsonError {
+            line,
+            column,
+            message: format!("invalid number '{text}'"),
+        })
+    }
+
+    fn skip_digits(&mut self) {
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, JsonValue};
+
+    #[test]
+    fn test_parse() {
+        let json = parse("{\"a\": [1, -2.5e2, true, null],\n \"b\": \"x\\u00e9\\n\"}").unwrap();
+        let a = json.get("a").unwrap().as_array().unwrap();
+        assert_eq!(a.len(), 4);
+        assert_eq!(a[0].as_f64().unwrap(), 1.0);
+        assert_eq!(a[1].as_f64().unwrap(), -250.0);
+        assert!(a[2].as_bool().unwrap());
+        assert!(matches!(a[3].value, JsonValue::Null));
+
+        let b = json.get("b").unwrap();
+        assert_eq!(b.as_str().unwrap(), "xé\n");
+        assert_eq!((b.line, b.column), (2, 7));
+    }
+
+    #[test]
+    fn test_error_position() {
+        let error = parse("{\n  \"a\": [1, 2,]\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 14));
+
+        let error = parse("{\"a\": 1, \"a\": 2}").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 10));
+    }
+}
@@ -1,65 +1,230 @@
+pub mod background;
 pub mod camera;
+pub mod cli;
+pub mod density;
 pub mod hittable;
 pub mod image;
+pub mod integrator;
+pub mod json;
+pub mod light;
 pub mod material;
+pub mod obj;
+pub mod perlin;
 pub mod ray;
+pub mod sampling;
+pub mod scene;
+pub mod texture;
+pub mod transform;
 pub mod vec3;
 pub mod bad_rand;
 
-use hittable::{Hittable, sphere::Sphere, HitRecord};
+use hittable::{sphere::Sphere, Hittable};
 use camera::Camera;
-use image::{Image, Pixel};
-use ray::Ray;
+use image::HdrImage;
+use integrator::{Aov, AovIntegrator, Integrator, IntegratorKind};
+use scene::Scene;
 use vec3::Vec3;
 use material::{Material, Lambertian, Dielectric, Metal};
-use std::{rc::Rc, iter::repeat_with};
+use std::{iter::repeat_with, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
 
-pub fn render(
-    image_height: usize,
-    image_width: usize,
-    samples_per_pixel: usize,
-    max_depth: usize,
-    camera: &Camera,
-    world: &Vec<Box<dyn Hittable>>,
-) -> Image {
-    let h_range = 0..image_height;
-    let w_range = 0..image_width;
-
-    Image::new(
-        image_height,
-        image_width,
-        h_range
-            .flat_map(|x| w_range.clone().map(move |y| (image_height - x - 1, y)))
-            .map(|(x, y)| {
-                repeat_with(|| bad_rand::rand_f64())
-                    .take(samples_per_pixel)
-                    .map(|random_val| {
-                        (
-                            (y as f64 + random_val) / (image_width as f64 - 1.0),
-                            (x as f64 + random_val) / (image_height as f64 - 1.0),
-                        )
-                    })
-                    .map(|(u, v)| camera.get_ray(u, v))
-                    .map(|ray| trace_ray(&ray, &world, max_depth))
-                    .map(|pixel| Vec3::new(pixel.r as f64, pixel.g as f64, pixel.b as f64))
-                    .fold(Vec3::new(0.0, 0.0, 0.0), |acc, v| acc + v)
-            }) // anti aliasing
-            .map(|pixel| pixel / samples_per_pixel as f64)
-            .map(|avg_sample| (avg_sample / 255.0).sqrt() * 256.0)
-            .map(|anti_aliased_pixel| Pixel::from_vec3(&anti_aliased_pixel))
+pub struct RenderSettings {
+    pub image_height: usize,
+    pub image_width: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    /// Number of worker threads used by the native renderer. Ignored on wasm.
+    pub threads: usize,
+    /// Seed for the sampling of the image. Every row draws from its own stream
+    /// so the result doesn't depend on the number of threads.
+    pub seed: u64,
+    pub integrator: IntegratorKind,
+    /// Passes that `raytracerbin` renders next to the image itself.
+    pub aovs: Vec<Aov>,
+    /// Number of bounces after which paths of the `PathTracer` are ended at
+    /// random, with a chance that grows as their throughput drops. Surviving
+    /// paths are weighted up, so the image stays unbiased.
+    pub roulette_depth: usize,
+}
+
+pub const DEFAULT_SEED: u64 = 123456789;
+pub const DEFAULT_ROULETTE_DEPTH: usize = 3;
+
+impl RenderSettings {
+    pub fn new(
+        image_height: usize,
+        image_width: usize,
+        samples_per_pixel: usize,
+        max_depth: usize,
+    ) -> RenderSettings {
+        RenderSettings {
+            image_height,
+            image_width,
+            samples_per_pixel,
+            max_depth,
+            threads: default_thread_count(),
+            seed: DEFAULT_SEED,
+            integrator: IntegratorKind::default(),
+            aovs: Vec::new(),
+            roulette_depth: DEFAULT_ROULETTE_DEPTH,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_thread_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn default_thread_count() -> usize {
+    1
+}
+
+pub fn render(scene: &Scene) -> HdrImage {
+    if let IntegratorKind::Aov(aov) = scene.settings.integrator {
+        return render_aov(scene, aov);
+    }
+    let integrator = scene.settings.integrator.build(&scene.settings);
+    render_with(scene, integrator.as_ref())
+}
+
+/// Renders one of the passes of `aov`. Object ids are looked up once through
+/// the middle of every pixel, since averaging them would make up the colors
+/// of objects that aren't there.
+pub fn render_aov(scene: &Scene, aov: Aov) -> HdrImage {
+    let integrator = AovIntegrator { aov };
+    match aov {
+        Aov::ObjectId => render_samples(scene, &integrator, false),
+        _ => render_with(scene, &integrator),
+    }
+}
+
+/// Renders `scene` with `integrator` instead of the one in its settings.
+pub fn render_with(scene: &Scene, integrator: &dyn Integrator) -> HdrImage {
+    render_samples(scene, integrator, true)
+}
+
+/// Spreads the samples of every pixel over its area if `jitter` is set, and
+/// takes a single sample through its middle otherwise.
+fn render_samples(scene: &Scene, integrator: &dyn Integrator, jitter: bool) -> HdrImage {
+    let settings = &scene.settings;
+    let camera = scene.camera.build();
+    HdrImage::new(
+        settings.image_height,
+        settings.image_width,
+        render_rows(scene, &camera, integrator, jitter)
+            .into_iter()
+            .flatten()
             .collect(),
     )
 }
 
+/// Renders the rows of the image on `settings.threads` scoped workers. Rows are
+/// handed out one at a time so that expensive parts of the scene don't leave
+/// the other workers idle.
+#[cfg(not(target_arch = "wasm32"))]
+fn render_rows(
+    scene: &Scene,
+    camera: &Camera,
+    integrator: &dyn Integrator,
+    jitter: bool,
+) -> Vec<Vec<Vec3>> {
+    let settings = &scene.settings;
+    let threads = settings.threads.clamp(1, settings.image_height.max(1));
+    let next_row = AtomicUsize::new(0);
+    let mut rows = vec![Vec::new(); settings.image_height];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished_rows = Vec::new();
+                    loop {
+                        let row = next_row.fetch_add(1, Ordering::Relaxed);
+                        if row >= settings.image_height {
+                            return finished_rows;
+                        }
+                        let pixels = render_row(row, scene, camera, integrator, jitter);
+                        finished_rows.push((row, pixels));
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            for (row, pixels) in worker.join().expect("render worker panicked") {
+                rows[row] = pixels;
+            }
+        }
+    });
+
+    rows
+}
+
+#[cfg(target_arch = "wasm32")]
+fn render_rows(
+    scene: &Scene,
+    camera: &Camera,
+    integrator: &dyn Integrator,
+    jitter: bool,
+) -> Vec<Vec<Vec3>> {
+    (0..scene.settings.image_height)
+        .map(|row| render_row(row, scene, camera, integrator, jitter))
+        .collect()
+}
+
+/// Renders one row of the image, counting rows from the top.
+fn render_row(
+    row: usize,
+    scene: &Scene,
+    camera: &Camera,
+    integrator: &dyn Integrator,
+    jitter: bool,
+) -> Vec<Vec3> {
+    let settings = &scene.settings;
+    let image_height = settings.image_height;
+    let image_width = settings.image_width;
+    let samples_per_pixel = if jitter { settings.samples_per_pixel } else { 1 };
+    let offset = || if jitter { bad_rand::rand_f64() } else { 0.5 };
+    let x = image_height - row - 1;
+    bad_rand::seed_stream(settings.seed, row as u64);
 
-fn get_random_material() -> Rc<dyn Material> {
-    let materials: Vec<Rc<dyn Material>> = vec![
-        Rc::new(Lambertian::new(&Vec3::new(
+    (0..image_width)
+        .map(|y| {
+            repeat_with(offset)
+                .take(samples_per_pixel)
+                // the samples cover the whole pixel, which also keeps an image
+                // that is one pixel wide or high from dividing by zero
+                .map(|random_val| {
+                    (
+                        (y as f64 + random_val) / image_width as f64,
+                        (x as f64 + random_val) / image_height as f64,
+                    )
+                })
+                .map(|(u, v)| camera.get_ray(u, v))
+                .map(|ray| integrator.radiance(&ray, scene))
+                .fold(Vec3::new(0.0, 0.0, 0.0), |acc, v| acc + v)
+        }) // anti aliasing
+        .map(|color| color / samples_per_pixel as f64)
+        .collect()
+}
+
+
+fn get_random_material() -> Arc<dyn Material> {
+    let materials: Vec<Arc<dyn Material>> = vec![
+        Arc::new(Lambertian::new(&Vec3::new(
             bad_rand::rand_f64(),
             bad_rand::rand_f64(),
             bad_rand::rand_f64(),
         ))),
-        Rc::new(Metal::new(
+        Arc::new(Metal::new(
             &Vec3::new(
                 0.5 + bad_rand::rand_f64() / 2.0,
                 0.5 + bad_rand::rand_f64() / 2.0,
@@ -67,7 +232,7 @@ fn get_random_material() -> Rc<dyn Material> {
             ),
             bad_rand::rand_f64() / 2.0,
         )),
-        Rc::new(Dielectric::new(1.5)),
+        Arc::new(Dielectric::new(1.5)),
     ];
 
     materials[bad_rand::rand_usize(0..materials.len())].clone()
@@ -79,22 +244,22 @@ pub fn create_random_scene() -> Vec<Box<dyn Hittable>> {
         Box::new(Sphere::new(
             &Vec3::new(0.0, -1000.0, 0.0),
             1000.0,
-            Rc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5))),
+            Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5))),
         )),
         Box::new(Sphere::new(
             &Vec3::new(0.0, 1.0, 0.0),
             1.0,
-            Rc::new(Dielectric::new(1.5)),
+            Arc::new(Dielectric::new(1.5)),
         )),
         Box::new(Sphere::new(
             &Vec3::new(-4.0, 1.0, 0.0),
             1.0,
-            Rc::new(Lambertian::new(&Vec3::new(0.4, 0.2, 0.1))),
+            Arc::new(Lambertian::new(&Vec3::new(0.4, 0.2, 0.1))),
         )),
         Box::new(Sphere::new(
             &Vec3::new(4.0, 1.0, 0.0),
             1.0,
-            Rc::new(Metal::new(&Vec3::new(0.7, 0.6, 0.5), 0.0)),
+            Arc::new(Metal::new(&Vec3::new(0.7, 0.6, 0.5), 0.0)),
         )),
     ];
 
@@ -109,7 +274,7 @@ pub fn create_random_scene() -> Vec<Box<dyn Hittable>> {
             )
         })
         .map(|center| Box::new(Sphere::new(&center, 0.2, get_random_material())))
-        .chain(large_spheres.into_iter())
+        .chain(large_spheres)
         .collect()
 }
 
@@ -124,81 +289,58 @@ impl FromIterator<Box<Sphere>> for Vec<Box<dyn Hittable>> {
 }
 
 
-fn trace_ray(r: &Ray, world: &Vec<Box<dyn Hittable>>, max_depth: usize) -> Pixel {
-    if max_depth <= 0 {
-        return Pixel::black();
-    }
-
-    let mut t_closest_so_far = f64::INFINITY;
-    let mut rec: Option<HitRecord> = None;
-
-    for obj in world {
-        let result = obj.hit(&r, 0.001, t_closest_so_far);
-
-        if let Some(temp_rec) = result {
-            t_closest_so_far = temp_rec.t_value();
-            rec = Some(temp_rec);
-        }
-    }
-
-    if let Some(final_rec) = rec {
-        if let Some((attenuation, new_ray)) = final_rec.material().scatter(r, final_rec) {
-            let pixel = trace_ray(&new_ray, world, max_depth - 1);
-
-            return Pixel::new(
-                (attenuation.x * pixel.r as f64) as u8,
-                (attenuation.y * pixel.g as f64) as u8,
-                (attenuation.z * pixel.b as f64) as u8,
-            );
-        }
-
-        return Pixel::black();
-    }
-
-    let w = 0.5 * (r.direction().y + 1.0);
-    let white: Vec3 = Vec3::new(1.0, 1.0, 1.0);
-    let blue: Vec3 = Vec3::new(0.5, 0.7, 1.0);
-    let color = white * (1.0 - w) + blue * w;
-    Pixel::new(
-        (color.x * 255.0) as u8,
-        (color.y * 255.0) as u8,
-        (color.z * 255.0) as u8,
-    )
-}
-
 extern crate wasm_bindgen;
 
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[wasm_bindgen]
 pub fn greet() -> String {
-    format!("Hello World!")
+    "Hello World!".to_string()
 }
 
 #[wasm_bindgen]
 pub fn render_image(image_height: usize, image_width: usize, samples_per_pixel: usize, max_depth: usize) -> Vec<u8> {
-    quad_rand::srand(123456789);
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-
-    let world: Vec<Box<dyn Hittable>> = create_random_scene();
-
-    let look_from = Vec3::new(13.0, 2.0, 3.0);
-    let look_at = Vec3::new(0.0, 0.0, 0.0);
-    let view_up = Vec3::new(0.0, 1.0, 0.0);
-    let vfov = 20.0;
-    let aperture = 0.1;
-    let focus_dist = 10.0;
-
-    let camera = Camera::new(
-        &look_from,
-        &look_at,
-        &view_up,
-        vfov,
-        ASPECT_RATIO,
-        aperture,
-        focus_dist,
-    );
-
-    render(image_height, image_width, samples_per_pixel, max_depth, &camera, &world)
+    bad_rand::seed(DEFAULT_SEED);
+
+    let mut scene = Scene::random();
+    scene.settings = RenderSettings::new(image_height, image_width, samples_per_pixel, max_depth);
+
+    render(&scene)
+        .to_image()
         .to_js_image_data()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{bad_rand, render, RenderSettings, DEFAULT_SEED};
+    use crate::scene::Scene;
+
+    #[test]
+    fn test_threads() {
+        bad_rand::seed(DEFAULT_SEED);
+        let mut scene = Scene::random();
+        scene.settings = RenderSettings::new(12, 16, 2, 4);
+
+        // rows draw from their own streams, so the workers don't matter
+        scene.settings.threads = 1;
+        let single = render(&scene);
+        scene.settings.threads = 4;
+        let parallel = render(&scene);
+        for (a, b) in single.pixels().iter().zip(parallel.pixels()) {
+            assert_eq!((a.x, a.y, a.z), (b.x, b.y, b.z));
+        }
+    }
+
+    #[test]
+    fn test_one_pixel_wide() {
+        bad_rand::seed(DEFAULT_SEED);
+        let mut scene = Scene::random();
+        scene.settings = RenderSettings::new(8, 1, 2, 4);
+
+        let image = render(&scene);
+        assert_eq!((image.width(), image.height()), (1, 8));
+        for pixel in image.pixels() {
+            assert!(pixel.x.is_finite() && pixel.y.is_finite() && pixel.z.is_finite());
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    background::{Background, EnvironmentMap},
+    bad_rand::rand_f64,
+    hittable::{sphere::Sphere, triangle::intersect},
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+use std::{f64::consts::PI, sync::Arc};
+
+/// A direction towards a light, as seen from some point in the scene.
+pub struct LightSample {
+    /// Unit vector from the point towards the light.
+    pub direction: Vec3,
+    /// How far along `direction` the light is. Infinite for lights that
+    /// surround the scene.
+    pub distance: f64,
+    /// Radiance leaving the light towards the point.
+    pub radiance: Vec3,
+    /// Density over solid angle with which `direction` was picked.
+    pub pdf: f64,
+}
+
+/// Something that gives off light and can be sampled directly, so that the
+/// renderer doesn't have to wait for scattered rays to find it by chance.
+pub trait Light: Send + Sync {
+    /// Picks a direction from `point` towards the light. `None` when the light
+    /// can't be seen from there at all.
+    fn sample(&self, point: &Vec3) -> Option<LightSample>;
+
+    /// Density with which `sample` picks `direction` from `point`, zero if it
+    /// misses the light.
+    fn pdf(&self, point: &Vec3, direction: &Vec3) -> f64;
+
+    /// How far `direction` goes from `point` before it reaches the light,
+    /// `None` if it misses. Tells apart lights that lie in the same
+    /// direction.
+    fn distance(&self, point: &Vec3, direction: &Vec3) -> Option<f64>;
+}
+
+/// An emissive sphere. Directions are sampled uniformly over the cone the
+/// sphere covers, so every sample lands on the visible side of it.
+pub struct SphereLight {
+    center: Vec3,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl SphereLight {
+    /// Should match a sphere in the scene, `material` being what it emits.
+    pub fn new(center: &Vec3, radius: f64, material: Arc<dyn Material>) -> SphereLight {
+        SphereLight {
+            center: *center,
+            radius,
+            material,
+        }
+    }
+
+    /// Cosine of the half angle of the cone the sphere covers as seen from
+    /// `point`, `None` from inside the sphere.
+    fn cos_theta_max(&self, point: &Vec3) -> Option<f64> {
+        let distance_squared = (self.center - *point).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let cos_theta_max = self.cos_theta_max(point)?;
+        let to_center = self.center - *point;
+        let distance_to_center = to_center.length();
+        let w = to_center / distance_to_center;
+        let (u, v) = w.orthonormal_basis();
+
+        let cos_theta = 1.0 - rand_f64() * (1.0 - cos_theta_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rand_f64();
+        let direction = u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + w * cos_theta;
+
+        // nearest intersection with the sphere, which grazing directions
+        // only miss through rounding
+        let half_chord_squared = self.radius * self.radius
+            - distance_to_center * distance_to_center * sin_theta * sin_theta;
+        let distance = distance_to_center * cos_theta - half_chord_squared.max(0.0).sqrt();
+        let light_point = *point + direction * distance;
+
+        let (tex_u, tex_v) = Sphere::get_uv(&((light_point - self.center) / self.radius));
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.material.emitted(tex_u, tex_v, &light_point),
+            pdf: 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+        })
+    }
+
+    fn pdf(&self, point: &Vec3, direction: &Vec3) -> f64 {
+        let cos_theta_max = match self.cos_theta_max(point) {
+            Some(cos_theta_max) => cos_theta_max,
+            None => return 0.0,
+        };
+        let to_center = (self.center - *point).normal();
+        if direction.normal().dot(&to_center) < cos_theta_max {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn distance(&self, point: &Vec3, direction: &Vec3) -> Option<f64> {
+        let to_center = self.center - *point;
+        let along = to_center.dot(&direction.normal());
+        let half_chord_squared =
+            self.radius * self.radius - (to_center.length_squared() - along * along);
+        if half_chord_squared < 0.0 {
+            return None;
+        }
+        // the far side for points inside of the sphere
+        let half_chord = half_chord_squared.sqrt();
+        [along - half_chord, along + half_chord]
+            .into_iter()
+            .find(|&distance| distance > 0.0)
+    }
+}
+
+/// An emissive triangle, sampled uniformly over its area. Emits from both
+/// sides, like `DiffuseLight` does.
+pub struct TriangleLight {
+    vertices: [Vec3; 3],
+    material: Arc<dyn Material>,
+}
+
+impl TriangleLight {
+    /// Should match a triangle in the scene, `material` being what it emits.
+    pub fn new(vertices: &[Vec3; 3], material: Arc<dyn Material>) -> TriangleLight {
+        TriangleLight {
+            vertices: *vertices,
+            material,
+        }
+    }
+
+    /// Converts the density over the area of the triangle to one over the
+    /// solid angle seen from a point `distance` away along `direction`.
+    fn solid_angle_pdf(&self, direction: &Vec3, distance: f64) -> f64 {
+        let [a, b, c] = &self.vertices;
+        let normal = (*b - *a).cross(&(*c - *a));
+        let area = normal.length() / 2.0;
+        let cosine = normal.normal().dot(direction).abs();
+        if area <= 0.0 || cosine <= 0.0 {
+            return 0.0;
+        }
+        distance * distance / (area * cosine)
+    }
+}
+
+impl Light for TriangleLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let [a, b, c] = &self.vertices;
+        // uniform barycentric coordinates
+        let su = rand_f64().sqrt();
+        let b1 = su * rand_f64();
+        let b2 = su - b1;
+        let light_point = *a + (*b - *a) * b1 + (*c - *a) * b2;
+
+        let to_light = light_point - *point;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let pdf = self.solid_angle_pdf(&direction, distance);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.material.emitted(b1, b2, &light_point),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, point: &Vec3, direction: &Vec3) -> f64 {
+        let [a, b, c] = &self.vertices;
+        let ray = Ray::new(point, direction);
+        match intersect(a, b, c, &ray, 0.0, f64::INFINITY) {
+            Some((distance, _, _)) => self.solid_angle_pdf(ray.direction(), distance),
+            None => 0.0,
+        }
+    }
+
+    fn distance(&self, point: &Vec3, direction: &Vec3) -> Option<f64> {
+        let [a, b, c] = &self.vertices;
+        intersect(a, b, c, &Ray::new(point, direction), 0.0, f64::INFINITY)
+            .map(|(distance, _, _)| distance)
+    }
+}
+
+/// An emissive disk, sampled uniformly over its area. Emits from both sides
+/// too.
+pub struct DiskLight {
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl DiskLight {
+    /// Should match a disk in the scene, `material` being what it emits.
+    pub fn new(
+        center: &Vec3,
+        normal: &Vec3,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> DiskLight {
+        DiskLight {
+            center: *center,
+            normal: normal.normal(),
+            radius,
+            material,
+        }
+    }
+
+    /// Same conversion as for triangles.
+    fn solid_angle_pdf(&self, direction: &Vec3, distance: f64) -> f64 {
+        let area = PI * self.radius * self.radius;
+        let cosine = self.normal.dot(direction).abs();
+        if area <= 0.0 || cosine <= 0.0 {
+            return 0.0;
+        }
+        distance * distance / (area * cosine)
+    }
+}
+
+impl Light for DiskLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let (u, v) = self.normal.orthonormal_basis();
+        // same surface coordinates as `Disk` gives the point
+        let tex_u = rand_f64();
+        let tex_v = rand_f64().sqrt();
+        let phi = 2.0 * PI * tex_u - PI;
+        let light_point = self.center + (u * phi.cos() + v * phi.sin()) * (self.radius * tex_v);
+
+        let to_light = light_point - *point;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let pdf = self.solid_angle_pdf(&direction, distance);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.material.emitted(tex_u, tex_v, &light_point),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, point: &Vec3, direction: &Vec3) -> f64 {
+        let direction = direction.normal();
+        match self.distance(point, &direction) {
+            Some(distance) => self.solid_angle_pdf(&direction, distance),
+            None => 0.0,
+        }
+    }
+
+    fn distance(&self, point: &Vec3, direction: &Vec3) -> Option<f64> {
+        let direction = direction.normal();
+        let denominator = self.normal.dot(&direction);
+        if denominator == 0.0 {
+            return None;
+        }
+        let distance = (self.center - *point).dot(&self.normal) / denominator;
+        if distance <= 0.0 || (*point + direction * distance - self.center).length() > self.radius {
+            return None;
+        }
+        Some(distance)
+    }
+}
+
+/// The environment lights the scene from infinitely far away.
+impl Light for EnvironmentMap {
+    fn sample(&self, _point: &Vec3) -> Option<LightSample> {
+        let (direction, pdf) = Background::sample(self)?;
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            radiance: self.value(&direction),
+            pdf,
+        })
+    }
+
+    fn pdf(&self, _point: &Vec3, direction: &Vec3) -> f64 {
+        Background::pdf(self, &direction.normal())
+    }
+
+    fn distance(&self, _point: &Vec3, _direction: &Vec3) -> Option<f64> {
+        Some(f64::INFINITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiskLight, Light, SphereLight, TriangleLight};
+    use crate::{bad_rand, material::DiffuseLight, vec3::Vec3};
+    use std::{f64::consts::PI, sync::Arc};
+
+    #[test]
+    fn test_sphere_light() {
+        bad_rand::seed(3);
+        let center = Vec3::new(1.0, 4.0, -2.0);
+        let light = SphereLight::new(
+            &center,
+            0.5,
+            Arc::new(DiffuseLight::new(&Vec3::new(3.0, 2.0, 1.0))),
+        );
+        let point = Vec3::new(0.0, 0.0, 0.0);
+
+        let distance_squared = center.length_squared();
+        let solid_angle = 2.0 * PI * (1.0 - (1.0 - 0.25 / distance_squared).sqrt());
+        for _ in 0..1000 {
+            let sample = light.sample(&point).unwrap();
+            let light_point = point + sample.direction * sample.distance;
+            assert!(((light_point - center).length() - 0.5).abs() < 1e-9);
+            // the point faces the sampled position
+            assert!((light_point - center).dot(&sample.direction) <= 1e-9);
+            assert!((sample.pdf - 1.0 / solid_angle).abs() < 1e-9);
+            assert!((light.pdf(&point, &sample.direction) - sample.pdf).abs() < 1e-9);
+            let distance = light.distance(&point, &sample.direction).unwrap();
+            assert!((distance - sample.distance).abs() < 1e-9);
+            assert_eq!(sample.radiance.x, 3.0);
+        }
+
+        assert_eq!(light.pdf(&point, &Vec3::new(0.0, -1.0, 0.0)), 0.0);
+        assert!(light.sample(&Vec3::new(1.0, 4.2, -2.0)).is_none());
+    }
+
+    #[test]
+    fn test_triangle_light() {
+        bad_rand::seed(5);
+        let vertices = [
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(1.0, 2.0, -1.0),
+            Vec3::new(0.0, 2.0, 1.0),
+        ];
+        let light = TriangleLight::new(
+            &vertices,
+            Arc::new(DiffuseLight::new(&Vec3::new(1.0, 1.0, 1.0))),
+        );
+        let point = Vec3::new(0.5, 0.0, 0.0);
+
+        let samples = 20000;
+        let mut solid_angle = 0.0;
+        for _ in 0..samples {
+            let sample = light.sample(&point).unwrap();
+            let light_point = point + sample.direction * sample.distance;
+            assert!((light_point.y - 2.0).abs() < 1e-9);
+            assert!((light.pdf(&point, &sample.direction) - sample.pdf).abs() < 1e-6 * sample.pdf);
+            let distance = light.distance(&point, &sample.direction).unwrap();
+            assert!((distance - sample.distance).abs() < 1e-9);
+            solid_angle += 1.0 / sample.pdf / samples as f64;
+        }
+
+        // Van Oosterom and Strackee
+        let [a, b, c] = vertices.map(|v| v - point);
+        let (la, lb, lc) = (a.length(), b.length(), c.length());
+        let expected = 2.0
+            * a.dot(&b.cross(&c))
+                .abs()
+                .atan2(la * lb * lc + a.dot(&b) * lc + a.dot(&c) * lb + b.dot(&c) * la);
+        assert!((solid_angle - expected).abs() < 0.02 * expected);
+        assert_eq!(light.pdf(&point, &Vec3::new(0.0, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_disk_light() {
+        bad_rand::seed(7);
+        let center = Vec3::new(0.0, 3.0, 0.0);
+        let light = DiskLight::new(
+            &center,
+            &Vec3::new(0.0, -1.0, 0.0),
+            1.0,
+            Arc::new(DiffuseLight::new(&Vec3::new(1.0, 1.0, 1.0))),
+        );
+        let point = Vec3::new(0.0, 0.0, 0.0);
+
+        let samples = 20000;
+        let mut solid_angle = 0.0;
+        for _ in 0..samples {
+            let sample = light.sample(&point).unwrap();
+            let light_point = point + sample.direction * sample.distance;
+            assert!((light_point.y - 3.0).abs() < 1e-9);
+            assert!((light_point - center).length() <= 1.0 + 1e-9);
+            assert!((light.pdf(&point, &sample.direction) - sample.pdf).abs() < 1e-6 * sample.pdf);
+            let distance = light.distance(&point, &sample.direction).unwrap();
+            assert!((distance - sample.distance).abs() < 1e-9);
+            solid_angle += 1.0 / sample.pdf / samples as f64;
+        }
+
+        // cone over a disk straight above the point
+        let expected = 2.0 * PI * (1.0 - 3.0 / 10.0_f64.sqrt());
+        assert!((solid_angle - expected).abs() < 0.02 * expected);
+        assert_eq!(light.pdf(&point, &Vec3::new(1.0, 1.0, 0.0)), 0.0);
+        assert_eq!(light.pdf(&point, &Vec3::new(0.0, -1.0, 0.0)), 0.0);
+    }
+}
@@ -1,68 +1,166 @@
 use crate::{
     hittable::HitRecord, 
     ray::Ray, 
+    texture::{SolidColor, Texture},
     vec3::Vec3,
     bad_rand::rand_f64
 };
+use std::{f64::consts::PI, sync::Arc};
 
-pub trait Material {
+/// How light scatters off a surface. Besides picking a direction to continue
+/// a path in, materials can evaluate how much light they reflect between two
+/// given directions, so that lights can be sampled directly as well.
+pub trait Material: Send + Sync {
+    /// Picks a direction to continue `r` in after it hit the surface. The
+    /// attenuation is `eval` over `pdf` for the picked direction, or the
+    /// color of the reflection for materials that scatter into a single
+    /// direction.
     fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)>;
+
+    /// Fraction of the radiance arriving from `direction` that leaves back
+    /// along `r`, times the cosine between `direction` and the normal. Black
+    /// for materials that scatter into a single direction.
+    fn eval(&self, _r: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Density, over solid angle, with which `scatter` picks `direction`.
+    /// Zero for materials that scatter into a single direction, like mirrors
+    /// and glass, which can't be sampled towards a light.
+    fn pdf(&self, _r: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Radiance given off at `point`, with `u` and `v` the surface coordinates
+    /// of the hit. Most materials don't emit any light.
+    fn emitted(&self, _u: f64, _v: f64, _point: &Vec3) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Color of the surface at the hit, for the albedo pass. Black for
+    /// materials that don't reflect light.
+    fn albedo(&self, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Whether `emitted` can return anything but black, which makes the
+    /// objects using the material worth sampling as lights.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: &Vec3) -> Lambertian {
-        Lambertian { albedo: *albedo }
+        Lambertian::with_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>) -> Lambertian {
+        Lambertian { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
-        let scatter_direction = *hit_record.normal() + Vec3::get_random_point_on_unit_circle();
+    fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
+        let (u, v) = hit_record.uv();
+        let albedo = self.albedo.value(u, v, hit_record.point());
+        // cosine weighted around the normal
+        let scatter_direction = *hit_record.normal() + Vec3::random_unit_vector();
         if scatter_direction.is_near_zero() {
             Some((
-                self.albedo,
-                Ray::new(hit_record.point(), hit_record.normal()),
+                albedo,
+                Ray::with_time(hit_record.point(), hit_record.normal(), r.time()),
             ))
         } else {
             Some((
-                self.albedo,
-                Ray::new(hit_record.point(), &scatter_direction),
+                albedo,
+                Ray::with_time(hit_record.point(), &scatter_direction, r.time()),
             ))
         }
     }
+
+    fn eval(&self, r: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Vec3 {
+        let (u, v) = hit_record.uv();
+        self.albedo.value(u, v, hit_record.point()) * self.pdf(r, hit_record, direction)
+    }
+
+    fn pdf(&self, _r: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let cosine = hit_record.normal().dot(&direction.normal());
+        cosine.max(0.0) / PI
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        let (u, v) = hit_record.uv();
+        self.albedo.value(u, v, hit_record.point())
+    }
 }
 
 pub struct Metal {
-    albedo: Vec3,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: &Vec3, fuzz: f64) -> Metal {
-        Metal {
-            albedo: *albedo,
-            fuzz,
-        }
+        Metal::with_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Metal {
+        Metal { albedo, fuzz }
     }
 }
 
 impl Material for Metal {
     fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
-        let reflected_direction = reflect(r.direction(), hit_record.normal());
+        let reflected_direction = reflect(r.direction(), hit_record.normal())
+            + Vec3::get_random_point_on_unit_circle() * self.fuzz;
         if reflected_direction.dot(hit_record.normal()) > 0.0 {
-            let reflected_ray = Ray::new(
-                hit_record.point(),
-                &(reflected_direction + Vec3::get_random_point_on_unit_circle() * self.fuzz),
-            );
-            Some((self.albedo, reflected_ray))
+            let (u, v) = hit_record.uv();
+            Some((
+                self.albedo.value(u, v, hit_record.point()),
+                Ray::with_time(hit_record.point(), &reflected_direction, r.time()),
+            ))
         } else {
             None
         }
     }
+
+    fn eval(&self, r: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Vec3 {
+        let (u, v) = hit_record.uv();
+        self.albedo.value(u, v, hit_record.point()) * self.pdf(r, hit_record, direction)
+    }
+
+    /// A perfect mirror without fuzz has no density. Otherwise `scatter`
+    /// offsets the mirror direction by a point in a ball of radius `fuzz`,
+    /// and the density of a direction is the part of that ball the direction
+    /// passes through, weighted by the squared distance from the hit.
+    fn pdf(&self, r: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let direction = direction.normal();
+        if self.fuzz <= 0.0 || direction.dot(hit_record.normal()) <= 0.0 {
+            return 0.0;
+        }
+        let reflected_direction = reflect(&r.direction().normal(), hit_record.normal());
+        let cosine = direction.dot(&reflected_direction);
+        let discriminant = cosine * cosine - 1.0 + self.fuzz * self.fuzz;
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+        let far = cosine + discriminant.sqrt();
+        let near = (cosine - discriminant.sqrt()).max(0.0);
+        if far <= 0.0 {
+            return 0.0;
+        }
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        let (u, v) = hit_record.uv();
+        self.albedo.value(u, v, hit_record.point())
+    }
 }
 
 fn reflect(v_in: &Vec3, normal: &Vec3) -> Vec3 {
@@ -81,7 +179,7 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
-        let cos_theta = (*r.direction() * -1.0).dot(&hit_record.normal());
+        let cos_theta = (*r.direction() * -1.0).dot(hit_record.normal());
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let refraction_ratio = if hit_record.front_face() {
@@ -104,9 +202,13 @@ impl Material for Dielectric {
         };
         Some((
             Vec3::new(1.0, 1.0, 1.0),
-            Ray::new(hit_record.point(), &new_direction),
+            Ray::with_time(hit_record.point(), &new_direction, r.time()),
         ))
     }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0)
+    }
 }
 
 fn refract(v_in: &Vec3, normal: &Vec3, etai_over_etat: f64) -> Vec3 {
@@ -122,3 +224,248 @@ fn reflectance(cosine: f64, ref_index: f64) -> f64 {
     let r0 = r0 * r0;
     r0 + (1.0 - r0) * ((1.0 - cosine).powi(5))
 }
+
+/// Turns whatever it is applied to into a light source that emits `emit`
+/// evenly in all directions and doesn't reflect anything.
+pub struct DiffuseLight {
+    emit: Vec3,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: &Vec3) -> DiffuseLight {
+        DiffuseLight { emit: *emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r: &Ray, _hit_record: HitRecord) -> Option<(Vec3, Ray)> {
+        None
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _point: &Vec3) -> Vec3 {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        !self.emit.is_near_zero()
+    }
+}
+
+/// Phase function of a participating medium that scatters light equally in
+/// all directions. Hits in a medium have no surface, so the normal of the
+/// hit plays no part in it.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: &Vec3) -> Isotropic {
+        Isotropic::with_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
+        Some((
+            self.albedo(&hit_record),
+            Ray::with_time(hit_record.point(), &Vec3::random_unit_vector(), r.time()),
+        ))
+    }
+
+    fn eval(&self, r: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Vec3 {
+        self.albedo(hit_record) * self.pdf(r, hit_record, direction)
+    }
+
+    fn pdf(&self, _r: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        let (u, v) = hit_record.uv();
+        self.albedo.value(u, v, hit_record.point())
+    }
+}
+
+/// Phase function of a medium that scatters light mostly forwards, like
+/// clouds and haze, or mostly backwards. `g` in `(-1, 1)` is the average
+/// cosine between the directions before and after scattering, 0 scatters
+/// in all directions evenly like `Isotropic`.
+pub struct HenyeyGreenstein {
+    albedo: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: &Vec3, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein::with_texture(Arc::new(SolidColor::new(albedo)), g)
+    }
+
+    pub fn with_texture(albedo: Arc<dyn Texture>, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein { albedo, g }
+    }
+
+    /// Density of scattering by an angle with the given cosine.
+    fn phase(&self, cosine: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cosine;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r: &Ray, hit_record: HitRecord) -> Option<(Vec3, Ray)> {
+        let g = self.g;
+        let cosine = if g.abs() < 1e-3 {
+            1.0 - 2.0 * rand_f64()
+        } else {
+            // inverts the cumulative distribution of the cosine
+            let fraction = (1.0 - g * g) / (1.0 - g + 2.0 * g * rand_f64());
+            ((1.0 + g * g - fraction * fraction) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let phi = 2.0 * PI * rand_f64();
+        let forward = r.direction().normal();
+        let (u, v) = forward.orthonormal_basis();
+        let direction = forward * cosine + (u * phi.cos() + v * phi.sin()) * sine;
+        Some((
+            self.albedo(&hit_record),
+            Ray::with_time(hit_record.point(), &direction, r.time()),
+        ))
+    }
+
+    fn eval(&self, r: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Vec3 {
+        self.albedo(hit_record) * self.pdf(r, hit_record, direction)
+    }
+
+    fn pdf(&self, r: &Ray, _hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        self.phase(r.direction().normal().dot(&direction.normal()))
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Vec3 {
+        let (u, v) = hit_record.uv();
+        self.albedo.value(u, v, hit_record.point())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HenyeyGreenstein, Isotropic, Lambertian, Material, Metal};
+    use crate::{bad_rand, hittable::HitRecord, ray::Ray, vec3::Vec3};
+    use std::{f64::consts::PI, sync::Arc};
+
+    /// Integrates the density of `material` over all directions by uniform
+    /// sampling, and counts how often `scatter` keeps the path going.
+    fn check_pdf(material: Arc<dyn Material>) -> (f64, f64) {
+        let r = Ray::new(&Vec3::new(-1.0, 1.0, 0.0), &Vec3::new(1.0, -1.0, 0.0));
+        let rec = HitRecord::new(
+            &Vec3::new(0.0, 0.0, 0.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+            2f64.sqrt(),
+            true,
+            (0.0, 0.0),
+            material.clone(),
+        );
+
+        let samples = 200000;
+        let mut integral = 0.0;
+        let mut scattered = 0;
+        for _ in 0..samples {
+            let direction = Vec3::random_unit_vector();
+            let pdf = material.pdf(&r, &rec, &direction);
+            let eval = material.eval(&r, &rec, &direction);
+            assert!((eval.x - 0.5 * pdf).abs() < 1e-9);
+            integral += pdf * 4.0 * PI / samples as f64;
+
+            if let Some((attenuation, new_ray)) = material.scatter(&r, rec.clone()) {
+                assert!(material.pdf(&r, &rec, new_ray.direction()) > 0.0);
+                assert_eq!(attenuation.x, 0.5);
+                scattered += 1;
+            }
+        }
+        (integral, scattered as f64 / samples as f64)
+    }
+
+    #[test]
+    fn test_lambertian_pdf() {
+        bad_rand::seed(1);
+        let (integral, scattered) = check_pdf(Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5))));
+        assert!((integral - 1.0).abs() < 0.01);
+        assert_eq!(scattered, 1.0);
+    }
+
+    #[test]
+    fn test_lambertian_cosine_weighted() {
+        bad_rand::seed(5);
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5)));
+        let r = Ray::new(&Vec3::new(0.0, 1.0, 0.0), &Vec3::new(0.0, -1.0, 0.0));
+        let rec = HitRecord::new(
+            &Vec3::new(0.0, 0.0, 0.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+            (0.0, 0.0),
+            material.clone(),
+        );
+
+        // a density of cos / pi has E[cos] = 2/3 and E[cos^2] = 1/2
+        let samples = 100000;
+        let (mut cosine, mut cosine_squared) = (0.0, 0.0);
+        for _ in 0..samples {
+            let (_, scattered) = material.scatter(&r, rec.clone()).unwrap();
+            let y = scattered.direction().normal().y;
+            cosine += y / samples as f64;
+            cosine_squared += y * y / samples as f64;
+        }
+        assert!((cosine - 2.0 / 3.0).abs() < 0.01);
+        assert!((cosine_squared - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_metal_pdf() {
+        bad_rand::seed(2);
+        // the fuzz pushes some of the reflections below the surface
+        let (integral, scattered) = check_pdf(Arc::new(Metal::new(&Vec3::new(0.5, 0.5, 0.5), 0.9)));
+        assert!(scattered < 0.99);
+        assert!((integral - scattered).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_isotropic_pdf() {
+        bad_rand::seed(3);
+        let (integral, scattered) = check_pdf(Arc::new(Isotropic::new(&Vec3::new(0.5, 0.5, 0.5))));
+        assert!((integral - 1.0).abs() < 1e-9);
+        assert_eq!(scattered, 1.0);
+    }
+
+    #[test]
+    fn test_henyey_greenstein_pdf() {
+        bad_rand::seed(4);
+        for g in [-0.5, 0.0, 0.8] {
+            let material = Arc::new(HenyeyGreenstein::new(&Vec3::new(0.5, 0.5, 0.5), g));
+            let (integral, scattered) = check_pdf(material.clone());
+            assert!((integral - 1.0).abs() < 0.03);
+            assert_eq!(scattered, 1.0);
+
+            // g is the average cosine of the scattering angle
+            let r = Ray::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(1.0, 0.0, 0.0));
+            let rec = HitRecord::new(
+                &Vec3::new(0.0, 0.0, 0.0),
+                &Vec3::new(-1.0, 0.0, 0.0),
+                1.0,
+                true,
+                (0.0, 0.0),
+                material.clone(),
+            );
+            let samples = 100000;
+            let mean_cosine = (0..samples)
+                .map(|_| material.scatter(&r, rec.clone()).unwrap().1.direction().x)
+                .sum::<f64>()
+                / samples as f64;
+            assert!((mean_cosine - g).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,607 @@
+use crate::{
+    hittable::mesh::{MeshFace, TriangleMesh},
+    image::{Filter, HdrImage, Wrap},
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    texture::{ImageTexture, Texture},
+    vec3::Vec3,
+};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            ObjError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{file}:{line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// One mesh per group and material of the OBJ file.
+pub struct ObjGroup {
+    pub name: String,
+    pub material_name: Option<String>,
+    pub mesh: TriangleMesh,
+}
+
+/// Loads an OBJ file along with the MTL libraries it references. Faces without
+/// a `usemtl` statement get `default_material`, and a `usemtl` naming a
+/// material that none of the libraries define is an error.
+pub fn load(path: &Path, default_material: Arc<dyn Material>) -> Result<Vec<ObjGroup>, ObjError> {
+    let source = read_file(path)?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    parse_obj(
+        &source,
+        &path.display().to_string(),
+        base_dir,
+        default_material,
+    )
+}
+
+/// Parses OBJ source. `name` is used in error messages and `mtllib` paths are
+/// resolved against `base_dir`.
+pub fn parse_obj(
+    source: &str,
+    name: &str,
+    base_dir: &Path,
+    default_material: Arc<dyn Material>,
+) -> Result<Vec<ObjGroup>, ObjError> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    let mut group = String::from("default");
+    let mut material_name: Option<String> = None;
+    // faces collected for every (group, material) pair, in order of appearance
+    let mut batches: Vec<FaceBatch> = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let error = |message: String| ObjError::Parse {
+            file: name.to_string(),
+            line: line_number,
+            message,
+        };
+
+        let mut tokens = strip_comment(line).split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&arguments).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&arguments).map_err(error)?),
+            "vt" => {
+                let u = parse_number(arguments.first(), "texture coordinate").map_err(error)?;
+                // v is optional for one dimensional textures
+                let v = if arguments.len() > 1 {
+                    parse_number(arguments.get(1), "texture coordinate").map_err(error)?
+                } else {
+                    0.0
+                };
+                uvs.push((u, v));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error("a face needs at least three vertices".to_string()));
+                }
+                let vertices = arguments
+                    .iter()
+                    .map(|vertex| {
+                        parse_face_vertex(vertex, positions.len(), uvs.len(), normals.len())
+                    })
+                    .collect::<Result<Vec<FaceVertex>, String>>()
+                    .map_err(error)?;
+
+                let batch = match batches
+                    .iter_mut()
+                    .position(|b| b.group == group && b.material_name == material_name)
+                {
+                    Some(index) => &mut batches[index],
+                    None => {
+                        batches.push(FaceBatch {
+                            group: group.clone(),
+                            material_name: material_name.clone(),
+                            faces: Vec::new(),
+                        });
+                        batches.last_mut().unwrap()
+                    }
+                };
+                // triangulate the polygon as a fan around its first vertex
+                for i in 1..vertices.len() - 1 {
+                    batch
+                        .faces
+                        .push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                group = if arguments.is_empty() {
+                    String::from("default")
+                } else {
+                    arguments.join(" ")
+                };
+            }
+            "usemtl" => {
+                let requested = arguments.join(" ");
+                if !materials.contains_key(&requested) {
+                    return Err(error(format!("unknown material '{requested}'")));
+                }
+                material_name = Some(requested);
+            }
+            "mtllib" => {
+                for library in &arguments {
+                    let path = base_dir.join(library);
+                    let source = read_file(&path)?;
+                    materials.extend(parse_mtl(
+                        &source,
+                        &path.display().to_string(),
+                        path.parent().unwrap_or(base_dir),
+                    )?);
+                }
+            }
+            // smoothing groups, lines, points and the like don't affect triangles
+            _ => {}
+        }
+    }
+
+    Ok(batches
+        .into_iter()
+        .map(|batch| {
+            let material = match &batch.material_name {
+                Some(material_name) => materials[material_name].clone(),
+                None => default_material.clone(),
+            };
+            ObjGroup {
+                mesh: batch.build_mesh(&positions, &normals, &uvs, material),
+                name: batch.group,
+                material_name: batch.material_name,
+            }
+        })
+        .collect())
+}
+
+/// Parses an MTL library, mapping each material onto the closest of the
+/// built-in materials:
+///
+/// * materials with an emission color `Ke` become `DiffuseLight`,
+/// * transparent materials (`d` below 1 or a refracting `illum`) become
+///   `Dielectric` with index `Ni`,
+/// * reflective ones (`illum` 3, 5 or 8, or only a specular color) become
+///   `Metal` with albedo `Ks` and a fuzz derived from the exponent `Ns`,
+/// * everything else is `Lambertian` with albedo `Kd`, or the image `map_Kd`
+///   if it has one. Texture paths are resolved against `base_dir`.
+pub fn parse_mtl(
+    source: &str,
+    name: &str,
+    base_dir: &Path,
+) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    Ok(read_mtl(source, name, base_dir)?
+        .into_iter()
+        .map(|material| (material.name.clone(), material.build()))
+        .collect())
+}
+
+/// The materials of an MTL library as written, before they are mapped onto
+/// built-in ones.
+fn read_mtl(source: &str, name: &str, base_dir: &Path) -> Result<Vec<MtlMaterial>, ObjError> {
+    let mut materials = Vec::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse {
+            file: name.to_string(),
+            line: line_index + 1,
+            message,
+        };
+
+        let mut tokens = strip_comment(line).split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            materials.extend(current.take());
+            current = Some(MtlMaterial::new(arguments.join(" ")));
+            continue;
+        }
+
+        let material = match (&mut current, keyword) {
+            (Some(material), _) => material,
+            (None, "Kd" | "Ks" | "Ke" | "map_Kd" | "Ns" | "Ni" | "d" | "Tr" | "illum") => {
+                return Err(error(format!("'{keyword}' before any 'newmtl'")))
+            }
+            (None, _) => continue,
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_vec3(&arguments).map_err(error)?,
+            "Ks" => material.specular = parse_vec3(&arguments).map_err(error)?,
+            "Ke" => material.emission = parse_vec3(&arguments).map_err(error)?,
+            "map_Kd" => {
+                // options like -s come before the file name
+                let file = arguments
+                    .last()
+                    .ok_or_else(|| error("'map_Kd' needs a file name".to_string()))?;
+                let image = HdrImage::load(&base_dir.join(file))
+                    .map_err(|image_error| error(image_error.to_string()))?;
+                material.diffuse_map = Some(Arc::new(ImageTexture::new(
+                    image,
+                    Filter::Bilinear,
+                    Wrap::Repeat,
+                )));
+            }
+            "Ns" => material.exponent = parse_number(arguments.first(), "Ns").map_err(error)?,
+            "Ni" => {
+                material.refractive_index = parse_number(arguments.first(), "Ni").map_err(error)?
+            }
+            "d" => material.dissolve = parse_number(arguments.first(), "d").map_err(error)?,
+            "Tr" => {
+                material.dissolve = 1.0 - parse_number(arguments.first(), "Tr").map_err(error)?
+            }
+            "illum" => {
+                material.illum = parse_number(arguments.first(), "illum").map_err(error)? as u32
+            }
+            // texture maps and unsupported parameters
+            _ => {}
+        }
+    }
+
+    materials.extend(current.take());
+    Ok(materials)
+}
+
+struct MtlMaterial {
+    name: String,
+    diffuse: Vec3,
+    specular: Vec3,
+    emission: Vec3,
+    diffuse_map: Option<Arc<dyn Texture>>,
+    exponent: f64,
+    refractive_index: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> MtlMaterial {
+        MtlMaterial {
+            name,
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            emission: Vec3::new(0.0, 0.0, 0.0),
+            diffuse_map: None,
+            exponent: 0.0,
+            refractive_index: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+
+    fn build(&self) -> Arc<dyn Material> {
+        let is_black = |c: &Vec3| c.x <= 0.0 && c.y <= 0.0 && c.z <= 0.0;
+
+        if !is_black(&self.emission) {
+            Arc::new(DiffuseLight::new(&self.emission))
+        } else if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            Arc::new(Dielectric::new(self.refractive_index))
+        } else if matches!(self.illum, 3 | 5 | 8)
+            || (is_black(&self.diffuse) && !is_black(&self.specular))
+        {
+            // rough approximation of the Phong lobe width as a fuzz radius
+            let fuzz = (2.0 / (self.exponent.max(0.0) + 2.0)).sqrt().min(1.0);
+            Arc::new(Metal::new(&self.specular, fuzz))
+        } else if let Some(diffuse_map) = &self.diffuse_map {
+            Arc::new(Lambertian::with_texture(diffuse_map.clone()))
+        } else {
+            Arc::new(Lambertian::new(&self.diffuse))
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct FaceBatch {
+    group: String,
+    material_name: Option<String>,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+impl FaceBatch {
+    /// Builds a mesh holding only the vertices this batch refers to.
+    fn build_mesh(
+        &self,
+        positions: &[Vec3],
+        normals: &[Vec3],
+        uvs: &[(f64, f64)],
+        material: Arc<dyn Material>,
+    ) -> TriangleMesh {
+        let mut position_map = IndexMap::default();
+        let mut normal_map = IndexMap::default();
+        let mut uv_map = IndexMap::default();
+
+        let faces = self
+            .faces
+            .iter()
+            .map(|face| MeshFace {
+                positions: face.map(|v| position_map.remap(v.position)),
+                // a face is only smooth shaded or textured if every vertex says so
+                normals: if face.iter().all(|v| v.normal.is_some()) {
+                    Some(face.map(|v| normal_map.remap(v.normal.unwrap())))
+                } else {
+                    None
+                },
+                uvs: if face.iter().all(|v| v.uv.is_some()) {
+                    Some(face.map(|v| uv_map.remap(v.uv.unwrap())))
+                } else {
+                    None
+                },
+            })
+            .collect();
+
+        TriangleMesh::new(
+            position_map.gather(positions),
+            normal_map.gather(normals),
+            uv_map.gather(uvs),
+            faces,
+            material,
+        )
+    }
+}
+
+#[derive(Default)]
+struct IndexMap {
+    new_indices: HashMap<usize, usize>,
+    old_indices: Vec<usize>,
+}
+
+impl IndexMap {
+    fn remap(&mut self, old_index: usize) -> usize {
+        let old_indices = &mut self.old_indices;
+        *self.new_indices.entry(old_index).or_insert_with(|| {
+            old_indices.push(old_index);
+            old_indices.len() - 1
+        })
+    }
+
+    fn gather<T: Copy>(&self, values: &[T]) -> Vec<T> {
+        self.old_indices.iter().map(|&i| values[i]).collect()
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|error| ObjError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(index) => &line[..index],
+        None => line,
+    }
+}
+
+fn parse_number(token: Option<&&str>, what: &str) -> Result<f64, String> {
+    let token = token.ok_or_else(|| format!("missing {what}"))?;
+    token
+        .parse::<f64>()
+        .map_err(|_| format!("invalid {what} '{token}'"))
+}
+
+fn parse_vec3(arguments: &[&str]) -> Result<Vec3, String> {
+    Ok(Vec3::new(
+        parse_number(arguments.first(), "x component")?,
+        parse_number(arguments.get(1), "y component")?,
+        parse_number(arguments.get(2), "z component")?,
+    ))
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero based indices, resolving
+/// negative indices relative to the end of the buffers read so far.
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = token.split('/');
+    let position = resolve_index(parts.next(), position_count, "vertex")?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(Some(index), uv_count, "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(index) => Some(resolve_index(Some(index), normal_count, "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex '{token}'"));
+    }
+
+    Ok(FaceVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+fn resolve_index(token: Option<&str>, count: usize, what: &str) -> Result<usize, String> {
+    let token = token.unwrap_or("");
+    let index: i64 = token
+        .parse()
+        .map_err(|_| format!("invalid {what} index '{token}'"))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{what} index {index} is out of range"));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use super::{parse_mtl, parse_obj, read_mtl, ObjError};
+    use crate::{
+        hittable::{HitRecord, Hittable},
+        material::{Lambertian, Material},
+        ray::Ray,
+        vec3::Vec3,
+    };
+
+    const QUADS: &str = "
+# two unit quads facing +z, the second one in its own group
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1 2/2 3/3 4/4
+g far
+v 0 0 -5
+v 1 0 -5
+v 1 1 -5
+v 0 1 -5
+f -4 -3 -2 -1
+";
+
+    #[test]
+    fn test_parse_obj() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5)));
+        let groups = parse_obj(QUADS, "quads.obj", Path::new("."), material).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "default");
+        assert_eq!(groups[1].name, "far");
+
+        let r = Ray::new(&Vec3::new(0.75, 0.5, 1.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = groups[0].mesh.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t_value() - 1.0).abs() < 1e-9);
+        let (u, v) = rec.uv();
+        assert!((u - 0.75).abs() < 1e-9);
+        assert!((v - 0.5).abs() < 1e-9);
+
+        let rec = groups[1].mesh.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert!((rec.t_value() - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5)));
+        let source = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        match parse_obj(source, "broken.obj", Path::new("."), material) {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let source = "
+newmtl matte
+Kd 0.1 0.2 0.3
+newmtl glass
+Ni 1.33
+d 0.2
+newmtl mirror
+Ks 0.9 0.8 0.7
+Ns 1000
+illum 3
+newmtl lamp
+Ke 10 10 8
+";
+        let records = read_mtl(source, "test.mtl", Path::new(".")).unwrap();
+        let names: Vec<&str> = records.iter().map(|record| record.name.as_str()).collect();
+        assert_eq!(names, ["matte", "glass", "mirror", "lamp"]);
+        let [matte, glass, mirror, lamp] = &records[..] else {
+            unreachable!()
+        };
+        assert_eq!(
+            (matte.diffuse.x, matte.diffuse.y, matte.diffuse.z),
+            (0.1, 0.2, 0.3)
+        );
+        assert_eq!((glass.refractive_index, glass.dissolve), (1.33, 0.2));
+        assert_eq!(
+            (mirror.specular.x, mirror.specular.y, mirror.specular.z),
+            (0.9, 0.8, 0.7)
+        );
+        assert_eq!((mirror.exponent, mirror.illum), (1000.0, 3));
+        assert_eq!(
+            (lamp.emission.x, lamp.emission.y, lamp.emission.z),
+            (10.0, 10.0, 8.0)
+        );
+
+        // each one is mapped onto the matching built-in material
+        let materials = parse_mtl(source, "test.mtl", Path::new(".")).unwrap();
+        let rec = |material: &Arc<dyn Material>| {
+            HitRecord::new(
+                &Vec3::new(0.0, 0.0, 0.0),
+                &Vec3::new(0.0, 0.0, 1.0),
+                1.0,
+                true,
+                (0.0, 0.0),
+                material.clone(),
+            )
+        };
+        let albedo = |name: &str| materials[name].albedo(&rec(&materials[name]));
+        assert_eq!(albedo("matte").y, 0.2);
+        assert_eq!(albedo("mirror").y, 0.8);
+        assert_eq!(albedo("glass").y, 1.0);
+        assert!(materials["lamp"].is_emissive());
+        let emitted = materials["lamp"].emitted(0.0, 0.0, &Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!((emitted.x, emitted.y, emitted.z), (10.0, 10.0, 8.0));
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let material = Arc::new(Lambertian::new(&Vec3::new(0.5, 0.5, 0.5)));
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl missing\nf 1 2 3\n";
+        match parse_obj(source, "missing.obj", Path::new("."), material) {
+            Err(error) => assert_eq!(
+                error.to_string(),
+                "missing.obj:4: unknown material 'missing'"
+            ),
+            Ok(_) => panic!("expected an unknown material error"),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{bad_rand, vec3::Vec3};
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise after Ken Perlin's "Improving Noise". The lattice gradients
+/// and permutations are drawn from `bad_rand`, so seeding it makes the noise
+/// reproducible.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new() -> Perlin {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = Vec3::get_random_point_on_unit_circle();
+                // normalizing very short vectors would amplify rounding errors
+                if v.length_squared() > 1e-4 {
+                    return v.normal();
+                }
+            })
+            .collect();
+        Perlin {
+            gradients,
+            permutations: [permutation(), permutation(), permutation()],
+        }
+    }
+
+    /// Smooth noise in `[-1, 1]` that is zero at every integer lattice point.
+    pub fn noise(&self, point: &Vec3) -> f64 {
+        let cell = [point.x.floor(), point.y.floor(), point.z.floor()];
+        let offset = [point.x - cell[0], point.y - cell[1], point.z - cell[2]];
+        // the tables repeat every POINT_COUNT cells
+        let cell = cell.map(|c| (c as i64).rem_euclid(POINT_COUNT as i64) as usize);
+        let fade = offset.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+        let mut sum = 0.0;
+        for corner in 0..8 {
+            let [i, j, k] = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let index = self.permutations[0][(cell[0] + i) % POINT_COUNT]
+                ^ self.permutations[1][(cell[1] + j) % POINT_COUNT]
+                ^ self.permutations[2][(cell[2] + k) % POINT_COUNT];
+            let to_point = Vec3::new(
+                offset[0] - i as f64,
+                offset[1] - j as f64,
+                offset[2] - k as f64,
+            );
+            let weight = |corner: usize, t: f64| if corner == 1 { t } else { 1.0 - t };
+            sum += weight(i, fade[0])
+                * weight(j, fade[1])
+                * weight(k, fade[2])
+                * self.gradients[index].dot(&to_point);
+        }
+        sum
+    }
+
+    /// Sum of the absolute noise over `octaves` doubling frequencies with
+    /// halving amplitudes. Always positive, with creases where the noise
+    /// crosses zero.
+    pub fn turbulence(&self, point: &Vec3, octaves: usize) -> f64 {
+        let mut sum = 0.0;
+        let mut point = *point;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            sum += weight * self.noise(&point).abs();
+            weight *= 0.5;
+            point = point * 2.0;
+        }
+        sum
+    }
+
+    /// Fractional Brownian motion: signed noise summed over `octaves`, each
+    /// `lacunarity` times the frequency and `gain` times the amplitude of the
+    /// previous one. The result is normalized to `[-1, 1]`.
+    pub fn fbm(&self, point: &Vec3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut sum = 0.0;
+        let mut total_weight = 0.0;
+        let mut point = *point;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            sum += weight * self.noise(&point);
+            total_weight += weight;
+            weight *= gain;
+            point = point * lacunarity;
+        }
+        if total_weight > 0.0 {
+            sum / total_weight
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Perlin {
+        Perlin::new()
+    }
+}
+
+/// A random shuffle of `0..POINT_COUNT`.
+fn permutation() -> Vec<usize> {
+    let mut values: Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+        values.swap(i, bad_rand::rand_usize(0..i + 1));
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Perlin;
+    use crate::{bad_rand, vec3::Vec3};
+
+    #[test]
+    fn test_noise() {
+        bad_rand::seed(7);
+        let perlin = Perlin::new();
+        assert_eq!(perlin.noise(&Vec3::new(3.0, -2.0, 17.0)), 0.0);
+
+        let mut max: f64 = 0.0;
+        for i in 0..1000 {
+            let t = i as f64 * 0.137;
+            let point = Vec3::new(t, t * 0.7 - 3.0, 5.0 - t * 0.3);
+            let value = perlin.noise(&point);
+            assert!((-1.0..=1.0).contains(&value));
+            max = max.max(value.abs());
+
+            let nearby = perlin.noise(&(point + 1e-6));
+            assert!((value - nearby).abs() < 1e-4);
+        }
+        assert!(max > 0.1);
+
+        bad_rand::seed(7);
+        let again = Perlin::new();
+        let point = Vec3::new(0.3, 1.7, -2.2);
+        assert_eq!(perlin.noise(&point), again.noise(&point));
+        assert!(perlin.turbulence(&point, 5) >= 0.0);
+        assert!(perlin.fbm(&point, 5, 2.0, 0.5).abs() <= 1.0);
+    }
+}
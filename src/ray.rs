@@ -4,13 +4,20 @@ use crate::vec3::Vec3;
 pub struct Ray {
     origin: Vec3,
     direction: Vec3,
+    time: f64,
 }
 
 impl Ray {
     pub fn new(origin: &Vec3, direction: &Vec3) -> Ray {
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    /// A ray that sees moving objects where they are at `time`.
+    pub fn with_time(origin: &Vec3, direction: &Vec3, time: f64) -> Ray {
         Ray {
             origin: *origin,
             direction: direction.normal(),
+            time,
         }
     }
 
@@ -25,9 +32,14 @@ impl Ray {
     pub fn origin(&self) -> &Vec3 {
         &self.origin
     }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::Ray;
     use crate::vec3::Vec3;
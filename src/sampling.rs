@@ -0,0 +1,152 @@
+/// A piecewise-constant distribution over `[0, 1)`, with one equally wide
+/// piece per value of the function it was built from.
+pub struct Distribution1D {
+    function: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// `function` must not be negative. If it is zero everywhere, the
+    /// distribution is uniform.
+    pub fn new(function: Vec<f64>) -> Distribution1D {
+        let n = function.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, value) in function.iter().enumerate() {
+            cdf.push(cdf[i] + value / n as f64);
+        }
+
+        let integral = cdf[n];
+        if integral > 0.0 {
+            for value in &mut cdf {
+                *value /= integral;
+            }
+        } else {
+            for (i, value) in cdf.iter_mut().enumerate() {
+                *value = i as f64 / n as f64;
+            }
+        }
+
+        Distribution1D {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform `u` in `[0, 1)` to a point distributed like the function.
+    /// Returns the point, its density and the piece it fell in.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.function.len();
+        // last entry of the cdf that is <= u, skipping empty pieces
+        let index = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        ((index as f64 + offset) / n as f64, self.pdf(index), index)
+    }
+
+    /// Density of the piece `index` with respect to `[0, 1)`.
+    pub fn pdf(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.function[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.function.len()
+    }
+}
+
+/// A piecewise-constant distribution over `[0, 1)²`, built from a grid of
+/// values stored row by row. Rows are picked first, then a column within the
+/// row.
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(function: &[f64], width: usize, height: usize) -> Distribution2D {
+        assert_eq!(function.len(), width * height);
+        let rows: Vec<Distribution1D> = function
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    /// Returns a point `(x, y)`, where `y` selects the row, and its density.
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (y, row_pdf, row) = self.marginal.sample(u2);
+        let (x, column_pdf, _) = self.rows[row].sample(u1);
+        ((x, y), row_pdf * column_pdf)
+    }
+
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let piece = |c: f64, n: usize| ((c * n as f64) as usize).min(n - 1);
+        let row = piece(y, self.marginal.count());
+        let column = piece(x, self.rows[row].count());
+        self.marginal.pdf(row) * self.rows[row].pdf(column)
+    }
+}
+
+/// Weight of a sample drawn with density `pdf` when another strategy could
+/// have produced it with density `other_pdf`, after Veach's power heuristic.
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{power_heuristic, Distribution1D, Distribution2D};
+
+    #[test]
+    fn test_distribution_1d() {
+        let distribution = Distribution1D::new(vec![1.0, 0.0, 3.0]);
+        assert_eq!(distribution.integral(), 4.0 / 3.0);
+
+        // a quarter of the mass lies in the first piece
+        let (x, pdf, index) = distribution.sample(0.125);
+        assert!((x - 1.0 / 6.0).abs() < 1e-12);
+        assert_eq!((pdf, index), (0.75, 0));
+
+        // the empty piece is skipped
+        let (x, pdf, index) = distribution.sample(0.25);
+        assert!((x - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!((pdf, index), (2.25, 2));
+
+        let uniform = Distribution1D::new(vec![0.0, 0.0]);
+        assert_eq!(uniform.sample(0.75), (0.75, 1.0, 1));
+    }
+
+    #[test]
+    fn test_distribution_2d() {
+        // 2x2 grid with all of the mass in the lower right cell
+        let distribution = Distribution2D::new(&[0.0, 0.0, 0.0, 5.0], 2, 2);
+        let ((x, y), pdf) = distribution.sample(0.3, 0.9);
+        assert!(x >= 0.5 && y >= 0.5);
+        assert_eq!(pdf, 4.0);
+        assert_eq!(distribution.pdf(0.75, 0.75), 4.0);
+        assert_eq!(distribution.pdf(0.25, 0.75), 0.0);
+
+        assert_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_eq!(power_heuristic(3.0, 1.0), 0.9);
+    }
+}
@@ -0,0 +1,1626 @@
+use crate::{
+    background::{Background, EnvironmentMap, GradientBackground, SolidBackground},
+    camera::CameraSettings,
+    create_random_scene,
+    density::{Density, GridDensity, NoiseDensity, VoxelGrid},
+    hittable::{
+        aa_box::AaBox, aabb::Aabb, bvh::Bvh, constant_medium::ConstantMedium, disk::Disk,
+        heterogeneous_medium::HeterogeneousMedium, identified::Identified, instance::Instance,
+        mesh::TriangleMesh, moving_sphere::MovingSphere, plane::Plane, quad::Quad, sphere::Sphere,
+        triangle::Triangle, Hittable,
+    },
+    image::{Filter, HdrImage, Wrap},
+    integrator::{Aov, IntegratorKind},
+    json::{self, Json, JsonError},
+    light::{DiskLight, Light, SphereLight, TriangleLight},
+    material::{
+        Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal,
+    },
+    obj,
+    texture::{
+        CheckerTexture, CloudTexture, ImageTexture, MarbleTexture, SolidColor, Texture, WoodTexture,
+    },
+    transform::{Motion, Transform},
+    vec3::Vec3,
+    RenderSettings,
+};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub const DEFAULT_IMAGE_HEIGHT: usize = 1200;
+pub const DEFAULT_SAMPLES_PER_PIXEL: usize = 100;
+pub const DEFAULT_MAX_DEPTH: usize = 50;
+
+/// Everything needed to render an image: where to look from, how to render
+/// and what to render.
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub settings: RenderSettings,
+    pub world: Box<dyn Hittable>,
+    pub background: Arc<dyn Background>,
+    /// Emitters that are sampled directly: emissive spheres, triangles,
+    /// quads, disks and boxes, which are part of `world` as well, and
+    /// environment maps, which are also the `background`.
+    pub lights: Vec<Arc<dyn Light>>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        file: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            SceneError::Parse {
+                file,
+                line,
+                column,
+                message,
+            } => write!(f, "{file}:{line}:{column}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    /// The field of random spheres the renderer has always shown.
+    pub fn random() -> Scene {
+        let camera = CameraSettings::default();
+        let image_width = (DEFAULT_IMAGE_HEIGHT as f64 * camera.aspect_ratio) as usize;
+        Scene {
+            camera,
+            settings: RenderSettings::new(
+                DEFAULT_IMAGE_HEIGHT,
+                image_width,
+                DEFAULT_SAMPLES_PER_PIXEL,
+                DEFAULT_MAX_DEPTH,
+            ),
+            world: Box::new(Bvh::new(identify(create_random_scene()))),
+            background: Arc::new(GradientBackground::default()),
+            lights: Vec::new(),
+        }
+    }
+
+    /// Loads a JSON scene description. Files it refers to are looked up
+    /// relative to the directory of `path`.
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        let source = fs::read_to_string(path).map_err(|error| SceneError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Scene::parse(&source, &path.display().to_string(), base_dir)
+    }
+
+    /// Parses a scene description. `name` is used in error messages.
+    ///
+    /// ```json
+    /// {
+    ///   "camera": { "look_from": [13, 2, 3], "look_at": [0, 0, 0], "vfov": 20 },
+    ///   "render": { "height": 400, "samples_per_pixel": 50 },
+    ///   "background": { "type": "environment", "path": "sky.hdr", "rotation": 90 },
+    ///   "materials": {
+    ///     "ground": { "type": "lambertian", "albedo": { "type": "checker",
+    ///       "even": [0.2, 0.3, 0.1], "odd": [0.9, 0.9, 0.9], "scale": 0.5 } }
+    ///   },
+    ///   "objects": [
+    ///     { "type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0], "material": "ground" },
+    ///     { "type": "sphere", "center": [0, 1, 0], "radius": 1,
+    ///       "material": { "type": "dielectric", "refractive_index": 1.5 } }
+    ///   ]
+    /// }
+    /// ```
+    pub fn parse(source: &str, name: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let to_scene_error = |error: JsonError| SceneError::Parse {
+            file: name.to_string(),
+            line: error.line,
+            column: error.column,
+            message: error.message,
+        };
+        let document = json::parse(source).map_err(to_scene_error)?;
+        SceneParser {
+            base_dir,
+            materials: HashMap::new(),
+            lights: Vec::new(),
+            meshes: HashMap::new(),
+            transform: None,
+            boundary_material: None,
+        }
+        .parse(&document)
+        .map_err(to_scene_error)
+    }
+}
+
+struct SceneParser<'a> {
+    base_dir: &'a Path,
+    materials: HashMap<String, Arc<dyn Material>>,
+    lights: Vec<Arc<dyn Light>>,
+    /// OBJ files by path, loaded once however often they are placed in the
+    /// scene. Each mesh comes with whether its faces lack a material of
+    /// their own, so that they take the one the scene gives.
+    meshes: HashMap<PathBuf, Vec<(Arc<TriangleMesh>, bool)>>,
+    /// Where the object being parsed is placed, which its lights have to
+    /// follow. Taken at time 0 for objects that move.
+    transform: Option<Transform>,
+    /// Material for objects that don't name one, set while parsing the
+    /// boundary of a medium, which is never seen itself.
+    boundary_material: Option<Arc<dyn Material>>,
+}
+
+impl SceneParser<'_> {
+    fn parse(mut self, document: &Json) -> Result<Scene, JsonError> {
+        check_fields(
+            document,
+            &["camera", "render", "materials", "objects", "background"],
+        )?;
+
+        if let Some(materials) = document.get("materials") {
+            for (name, definition) in materials.as_object()? {
+                let material = parse_material(definition, self.base_dir)?;
+                self.materials.insert(name.clone(), material);
+            }
+        }
+
+        let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+        if let Some(definitions) = document.get("objects") {
+            for definition in definitions.as_array()? {
+                self.parse_object(definition, &mut objects)?;
+            }
+        }
+
+        let render = document.get("render");
+        if let Some(render) = render {
+            check_fields(
+                render,
+                &[
+                    "width",
+                    "height",
+                    "samples_per_pixel",
+                    "max_depth",
+                    "threads",
+                    "seed",
+                    "integrator",
+                    "roulette_depth",
+                    "aovs",
+                ],
+            )?;
+        }
+        let render_field = |key: &str| render.and_then(|render| render.get(key));
+        let width = render_field("width").map(Json::as_usize).transpose()?;
+        let height = render_field("height").map(Json::as_usize).transpose()?;
+
+        let default_aspect_ratio = match (width, height) {
+            (Some(width), Some(height)) if height > 0 => width as f64 / height as f64,
+            _ => CameraSettings::default().aspect_ratio,
+        };
+        let camera = parse_camera(document.get("camera"), default_aspect_ratio)?;
+        let aspect_ratio = document
+            .get("camera")
+            .and_then(|camera| camera.get("aspect_ratio"));
+        if let (Some(width), Some(height), Some(aspect_ratio)) = (width, height, aspect_ratio) {
+            // the width only has to be right up to rounding to whole pixels
+            if (camera.aspect_ratio * height as f64 - width as f64).abs() >= 1.0 {
+                return Err(aspect_ratio.error(format!(
+                    "the aspect ratio doesn't match the image size of {width} x {height}"
+                )));
+            }
+        }
+
+        let (image_width, image_height) = match (width, height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, (width as f64 / camera.aspect_ratio) as usize),
+            (None, Some(height)) => ((height as f64 * camera.aspect_ratio) as usize, height),
+            (None, None) => (
+                (DEFAULT_IMAGE_HEIGHT as f64 * camera.aspect_ratio) as usize,
+                DEFAULT_IMAGE_HEIGHT,
+            ),
+        };
+        if image_width == 0 || image_height == 0 {
+            let location = render.unwrap_or(document);
+            return Err(location.error("the image must be at least one pixel wide and high"));
+        }
+
+        let mut settings = RenderSettings::new(
+            image_height,
+            image_width,
+            render_field("samples_per_pixel")
+                .map(Json::as_usize)
+                .transpose()?
+                .unwrap_or(DEFAULT_SAMPLES_PER_PIXEL),
+            render_field("max_depth")
+                .map(Json::as_usize)
+                .transpose()?
+                .unwrap_or(DEFAULT_MAX_DEPTH),
+        );
+        if let Some(threads) = render_field("threads") {
+            settings.threads = threads.as_usize()?.max(1);
+        }
+        if let Some(seed) = render_field("seed") {
+            settings.seed = seed.as_usize()? as u64;
+        }
+        if let Some(integrator) = render_field("integrator") {
+            settings.integrator = parse_integrator(integrator)?;
+        }
+        if let Some(aovs) = render_field("aovs") {
+            settings.aovs = aovs
+                .as_array()?
+                .iter()
+                .map(|aov| {
+                    let name = aov.as_str()?;
+                    Aov::from_name(name).ok_or_else(|| aov.error(format!("unknown pass '{name}'")))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(roulette_depth) = render_field("roulette_depth") {
+            settings.roulette_depth = roulette_depth.as_usize()?;
+        }
+        let background = match document.get("background") {
+            Some(background) => parse_background(background, self.base_dir, &mut self.lights)?,
+            None => Arc::new(GradientBackground::default()),
+        };
+
+        Ok(Scene {
+            camera,
+            settings,
+            world: Box::new(Bvh::new(identify(objects))),
+            background,
+            lights: self.lights,
+        })
+    }
+
+    /// A material is either the name of an entry in `materials` or an inline definition.
+    fn material(&self, json: &Json) -> Result<Arc<dyn Material>, JsonError> {
+        match json.as_str() {
+            Ok(name) => self
+                .materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| json.error(format!("unknown material '{name}'"))),
+            Err(_) => parse_material(json, self.base_dir),
+        }
+    }
+
+    /// The material of an object, which only boundaries of media may leave out.
+    fn object_material(&self, json: &Json) -> Result<Arc<dyn Material>, JsonError> {
+        match (json.get("material"), &self.boundary_material) {
+            (None, Some(material)) => Ok(material.clone()),
+            _ => self.material(required(json, "material")?),
+        }
+    }
+
+    fn push_triangle_light(&mut self, vertices: &[Vec3; 3], material: &Arc<dyn Material>) {
+        let vertices = match &self.transform {
+            Some(transform) => vertices.map(|vertex| transform.point(&vertex)),
+            None => *vertices,
+        };
+        self.lights
+            .push(Arc::new(TriangleLight::new(&vertices, material.clone())));
+    }
+
+    /// Emissive quads are sampled as the two triangles they are made of.
+    fn push_quad_lights(&mut self, quad: &Quad) {
+        if quad.material().is_emissive() {
+            for vertices in quad.triangles() {
+                self.push_triangle_light(&vertices, quad.material());
+            }
+        }
+    }
+
+    /// Where a round light ends up along with the scale of its radius.
+    /// Transforms that stretch it out of round leave it to be found by
+    /// scattered rays alone.
+    fn place_round_light(&self, center: &Vec3) -> Option<(Vec3, f64)> {
+        match &self.transform {
+            Some(transform) => Some((transform.point(center), transform.uniform_scale()?)),
+            None => Some((*center, 1.0)),
+        }
+    }
+
+    fn parse_object(
+        &mut self,
+        json: &Json,
+        objects: &mut Vec<Box<dyn Hittable>>,
+    ) -> Result<(), JsonError> {
+        let motion = json.get("transform").map(parse_transform).transpose()?;
+        self.transform = motion.as_ref().map(|motion| motion.at(0.0));
+        let first = objects.len();
+        let first_light = self.lights.len();
+        match object_type(json)? {
+            "sphere" => {
+                check_fields(json, &["type", "center", "radius", "material", "transform"])?;
+                let radius = required(json, "radius")?;
+                if radius.as_f64()? <= 0.0 {
+                    return Err(radius.error("the radius must be positive"));
+                }
+                let (center, center_end) = parse_animated(required(json, "center")?, parse_vec3)?;
+                let material = self.object_material(json)?;
+                if (center_end - center).length_squared() > 0.0 {
+                    // moving lights are left to be found by scattered rays
+                    objects.push(Box::new(MovingSphere::new(
+                        &center,
+                        &center_end,
+                        0.0,
+                        1.0,
+                        radius.as_f64()?,
+                        material,
+                    )));
+                } else {
+                    if material.is_emissive() {
+                        if let Some((light_center, scale)) = self.place_round_light(&center) {
+                            self.lights.push(Arc::new(SphereLight::new(
+                                &light_center,
+                                radius.as_f64()? * scale,
+                                material.clone(),
+                            )));
+                        }
+                    }
+                    objects.push(Box::new(Sphere::new(&center, radius.as_f64()?, material)));
+                }
+            }
+            "triangle" => {
+                check_fields(json, &["type", "vertices", "material", "transform"])?;
+                let vertices = required(json, "vertices")?;
+                let points = vertices
+                    .as_array()?
+                    .iter()
+                    .map(parse_vec3)
+                    .collect::<Result<Vec<Vec3>, JsonError>>()?;
+                if points.len() != 3 {
+                    return Err(vertices.error("a triangle needs exactly three vertices"));
+                }
+                let triangle = Triangle::new(
+                    &points[0],
+                    &points[1],
+                    &points[2],
+                    self.object_material(json)?,
+                );
+                if triangle.material().is_emissive() {
+                    self.push_triangle_light(triangle.vertices(), triangle.material());
+                }
+                objects.push(Box::new(triangle));
+            }
+            "plane" => {
+                check_fields(json, &["type", "point", "normal", "material", "transform"])?;
+                objects.push(Box::new(Plane::new(
+                    &parse_vec3(required(json, "point")?)?,
+                    &parse_direction(required(json, "normal")?)?,
+                    self.object_material(json)?,
+                )));
+            }
+            "quad" => {
+                check_fields(json, &["type", "corner", "u", "v", "material", "transform"])?;
+                let u = parse_vec3(required(json, "u")?)?;
+                let v_json = required(json, "v")?;
+                let v = parse_vec3(v_json)?;
+                if u.cross(&v).is_near_zero() {
+                    return Err(v_json.error("the edges of a quad must not be parallel"));
+                }
+                let quad = Quad::new(
+                    &parse_vec3(required(json, "corner")?)?,
+                    &u,
+                    &v,
+                    self.object_material(json)?,
+                );
+                self.push_quad_lights(&quad);
+                objects.push(Box::new(quad));
+            }
+            "disk" => {
+                check_fields(
+                    json,
+                    &[
+                        "type",
+                        "center",
+                        "normal",
+                        "radius",
+                        "material",
+                        "transform",
+                    ],
+                )?;
+                let radius = required(json, "radius")?;
+                if radius.as_f64()? <= 0.0 {
+                    return Err(radius.error("the radius must be positive"));
+                }
+                let disk = Disk::new(
+                    &parse_vec3(required(json, "center")?)?,
+                    &parse_direction(required(json, "normal")?)?,
+                    radius.as_f64()?,
+                    self.object_material(json)?,
+                );
+                if disk.material().is_emissive() {
+                    if let Some((center, scale)) = self.place_round_light(disk.center()) {
+                        let normal = match &self.transform {
+                            Some(transform) => transform.normal(disk.normal()),
+                            None => *disk.normal(),
+                        };
+                        self.lights.push(Arc::new(DiskLight::new(
+                            &center,
+                            &normal,
+                            disk.radius() * scale,
+                            disk.material().clone(),
+                        )));
+                    }
+                }
+                objects.push(Box::new(disk));
+            }
+            "box" => {
+                check_fields(json, &["type", "min", "max", "material", "transform"])?;
+                let min = parse_vec3(required(json, "min")?)?;
+                let max = parse_vec3(required(json, "max")?)?;
+                if (0..3).any(|axis| max[axis] <= min[axis]) {
+                    return Err(json.error("the box must extend along every axis"));
+                }
+                let aa_box = AaBox::new(&min, &max, self.object_material(json)?);
+                for face in aa_box.faces() {
+                    self.push_quad_lights(face);
+                }
+                objects.push(Box::new(aa_box));
+            }
+            "obj" => {
+                check_fields(json, &["type", "path", "material", "transform"])?;
+                let path = self.base_dir.join(required(json, "path")?.as_str()?);
+                let material = json
+                    .get("material")
+                    .map(|material| self.material(material))
+                    .transpose()?;
+                if !self.meshes.contains_key(&path) {
+                    let default_material = Arc::new(Lambertian::new(&Vec3::new(0.8, 0.8, 0.8)));
+                    let meshes = obj::load(&path, default_material)
+                        .map_err(|error| json.error(error.to_string()))?
+                        .into_iter()
+                        .map(|group| (Arc::new(group.mesh), group.material_name.is_none()))
+                        .collect();
+                    self.meshes.insert(path.clone(), meshes);
+                }
+                for (mesh, uses_default) in self.meshes[&path].clone() {
+                    // the material of the scene only goes to the faces the
+                    // MTL libraries leave without one, and the mesh stays
+                    // shared with the other placements of the file
+                    let scene_material = material.as_ref().filter(|_| uses_default);
+                    let mesh_material = scene_material.unwrap_or(mesh.material()).clone();
+                    if mesh_material.is_emissive() {
+                        for vertices in mesh.triangles() {
+                            self.push_triangle_light(&vertices, &mesh_material);
+                        }
+                    }
+                    objects.push(match scene_material {
+                        Some(material) => Box::new(
+                            Instance::new(mesh, Transform::identity())
+                                .with_material(material.clone()),
+                        ),
+                        None => Box::new(mesh),
+                    });
+                }
+            }
+            "medium" => {
+                check_fields(
+                    json,
+                    &["type", "boundary", "density", "material", "transform"],
+                )?;
+                let material = self.material(required(json, "material")?)?;
+                let mut boundary = Vec::new();
+                self.boundary_material = Some(material.clone());
+                let parsed = self.parse_object(required(json, "boundary")?, &mut boundary);
+                self.boundary_material = None;
+                parsed?;
+                // the boundary itself is never seen, so neither are its lights
+                self.lights.truncate(first_light);
+                let boundary: Box<dyn Hittable> = if boundary.len() == 1 {
+                    boundary.remove(0)
+                } else {
+                    Box::new(boundary)
+                };
+
+                // a number for the same density everywhere, or a density field
+                let density = required(json, "density")?;
+                match density.as_f64() {
+                    Ok(value) if value <= 0.0 => {
+                        return Err(density.error("the density must be positive"))
+                    }
+                    Ok(value) => {
+                        objects.push(Box::new(ConstantMedium::new(boundary, value, material)))
+                    }
+                    Err(_) => {
+                        let field = parse_density(density, boundary.bounding_box(), self.base_dir)?;
+                        objects.push(Box::new(HeterogeneousMedium::new(
+                            boundary, field, material,
+                        )));
+                    }
+                }
+            }
+            other => return Err(json.error(format!("unknown object type '{other}'"))),
+        }
+
+        self.place(motion, objects, first, first_light);
+        Ok(())
+    }
+
+    /// Moves the objects from `first` on, which make up the object just
+    /// parsed, into place.
+    fn place(
+        &mut self,
+        motion: Option<Motion>,
+        objects: &mut Vec<Box<dyn Hittable>>,
+        first: usize,
+        first_light: usize,
+    ) {
+        self.transform = None;
+        let motion = match motion {
+            Some(motion) => motion,
+            None => return,
+        };
+        let moving = !motion.is_fixed();
+        if moving {
+            // there is no one place to sample lights that move from, so they
+            // are left to scattered rays, which count them in full
+            self.lights.truncate(first_light);
+        }
+        let placed = objects.split_off(first).into_iter().map(|object| {
+            let instance = if moving {
+                Instance::moving(Arc::from(object), motion.clone())
+            } else {
+                Instance::new(Arc::from(object), motion.at(0.0))
+            };
+            Box::new(instance) as Box<dyn Hittable>
+        });
+        objects.extend(placed);
+    }
+}
+
+/// Numbers the objects from 1 on, in the order they were given.
+fn identify(objects: Vec<Box<dyn Hittable>>) -> Vec<Box<dyn Hittable>> {
+    objects
+        .into_iter()
+        .enumerate()
+        .map(|(i, object)| Box::new(Identified::new(i + 1, object)) as Box<dyn Hittable>)
+        .collect()
+}
+
+fn parse_camera(
+    json: Option<&Json>,
+    default_aspect_ratio: f64,
+) -> Result<CameraSettings, JsonError> {
+    let mut camera = CameraSettings {
+        aspect_ratio: default_aspect_ratio,
+        ..CameraSettings::default()
+    };
+    let json = match json {
+        Some(json) => json,
+        None => return Ok(camera),
+    };
+    check_fields(
+        json,
+        &[
+            "look_from",
+            "look_at",
+            "view_up",
+            "vfov",
+            "aspect_ratio",
+            "aperture",
+            "focus_dist",
+            "shutter",
+        ],
+    )?;
+
+    if let Some(v) = json.get("look_from") {
+        camera.look_from = parse_vec3(v)?;
+    }
+    if let Some(v) = json.get("look_at") {
+        camera.look_at = parse_vec3(v)?;
+    }
+    if let Some(v) = json.get("view_up") {
+        camera.view_up = parse_vec3(v)?;
+    }
+    if let Some(v) = json.get("vfov") {
+        camera.vertical_field_of_view_deg = v.as_f64()?;
+    }
+    if let Some(v) = json.get("aspect_ratio") {
+        camera.aspect_ratio = v.as_f64()?;
+        if camera.aspect_ratio <= 0.0 {
+            return Err(v.error("the aspect ratio must be positive"));
+        }
+    }
+    if let Some(v) = json.get("aperture") {
+        camera.aperture = v.as_f64()?;
+    }
+    if let Some(v) = json.get("focus_dist") {
+        camera.focus_dist = v.as_f64()?;
+    }
+    if let Some(v) = json.get("shutter") {
+        match v.as_array()? {
+            [open, close] => {
+                camera.shutter_open = open.as_f64()?;
+                camera.shutter_close = close.as_f64()?;
+            }
+            _ => return Err(v.error("expected the times the shutter opens and closes")),
+        }
+        if camera.shutter_close < camera.shutter_open {
+            return Err(v.error("the shutter must open before it closes"));
+        }
+    }
+    Ok(camera)
+}
+
+/// An integrator is either a name or an object with a `type` and the settings
+/// of that integrator.
+fn parse_integrator(json: &Json) -> Result<IntegratorKind, JsonError> {
+    let name = match json.as_str() {
+        Ok(name) => name,
+        Err(_) => object_type(json)?,
+    };
+    let integrator = IntegratorKind::from_name(name).ok_or_else(|| {
+        json.error(format!(
+            "unknown integrator '{name}', expected one of {}",
+            IntegratorKind::NAMES.join(", ")
+        ))
+    })?;
+    if json.as_str().is_ok() {
+        return Ok(integrator);
+    }
+
+    match integrator {
+        IntegratorKind::AmbientOcclusion { .. } => {
+            check_fields(json, &["type", "distance"])?;
+            let distance = match json.get("distance") {
+                Some(distance) => distance.as_f64()?,
+                None => f64::INFINITY,
+            };
+            if distance <= 0.0 {
+                return Err(json.error("the occlusion distance must be positive"));
+            }
+            Ok(IntegratorKind::AmbientOcclusion { distance })
+        }
+        _ => {
+            check_fields(json, &["type"])?;
+            Ok(integrator)
+        }
+    }
+}
+
+/// A background is either a plain color or an object with a `type`. Environment
+/// maps are added to `lights` as well.
+fn parse_background(
+    json: &Json,
+    base_dir: &Path,
+    lights: &mut Vec<Arc<dyn Light>>,
+) -> Result<Arc<dyn Background>, JsonError> {
+    if json.as_array().is_ok() {
+        return Ok(Arc::new(SolidBackground::new(&parse_vec3(json)?)));
+    }
+    match object_type(json)? {
+        "solid" => {
+            check_fields(json, &["type", "color"])?;
+            Ok(Arc::new(SolidBackground::new(&parse_vec3(required(
+                json, "color",
+            )?)?)))
+        }
+        "gradient" => {
+            check_fields(json, &["type", "bottom", "top"])?;
+            Ok(Arc::new(GradientBackground::new(
+                &optional_vec3(json, "bottom", Vec3::new(1.0, 1.0, 1.0))?,
+                &optional_vec3(json, "top", Vec3::new(0.5, 0.7, 1.0))?,
+            )))
+        }
+        "environment" => {
+            check_fields(json, &["type", "path", "rotation", "intensity"])?;
+            let path = base_dir.join(required(json, "path")?.as_str()?);
+            let image = HdrImage::load(&path).map_err(|error| json.error(error.to_string()))?;
+            let rotation = match json.get("rotation") {
+                Some(rotation) => rotation.as_f64()?.to_radians(),
+                None => 0.0,
+            };
+            let intensity = match json.get("intensity") {
+                Some(intensity) => intensity.as_f64()?,
+                None => 1.0,
+            };
+            let environment = Arc::new(EnvironmentMap::new(image, rotation, intensity));
+            lights.push(environment.clone());
+            Ok(environment)
+        }
+        other => Err(json.error(format!("unknown background type '{other}'"))),
+    }
+}
+
+/// Texture files are looked up relative to `base_dir`.
+fn parse_material(json: &Json, base_dir: &Path) -> Result<Arc<dyn Material>, JsonError> {
+    match object_type(json)? {
+        "lambertian" => {
+            check_fields(json, &["type", "albedo"])?;
+            Ok(Arc::new(Lambertian::with_texture(parse_texture(
+                required(json, "albedo")?,
+                base_dir,
+            )?)))
+        }
+        "metal" => {
+            check_fields(json, &["type", "albedo", "fuzz"])?;
+            let fuzz = match json.get("fuzz") {
+                Some(fuzz) => fuzz.as_f64()?,
+                None => 0.0,
+            };
+            Ok(Arc::new(Metal::with_texture(
+                parse_texture(required(json, "albedo")?, base_dir)?,
+                fuzz,
+            )))
+        }
+        "dielectric" => {
+            check_fields(json, &["type", "refractive_index"])?;
+            Ok(Arc::new(Dielectric::new(
+                required(json, "refractive_index")?.as_f64()?,
+            )))
+        }
+        "diffuse_light" => {
+            check_fields(json, &["type", "emit"])?;
+            Ok(Arc::new(DiffuseLight::new(&parse_vec3(required(
+                json, "emit",
+            )?)?)))
+        }
+        "isotropic" => {
+            check_fields(json, &["type", "albedo"])?;
+            Ok(Arc::new(Isotropic::with_texture(parse_texture(
+                required(json, "albedo")?,
+                base_dir,
+            )?)))
+        }
+        "henyey_greenstein" => {
+            check_fields(json, &["type", "albedo", "g"])?;
+            let g = required(json, "g")?;
+            if g.as_f64()?.abs() >= 1.0 {
+                return Err(g.error("g must be between -1 and 1"));
+            }
+            Ok(Arc::new(HenyeyGreenstein::with_texture(
+                parse_texture(required(json, "albedo")?, base_dir)?,
+                g.as_f64()?,
+            )))
+        }
+        other => Err(json.error(format!("unknown material type '{other}'"))),
+    }
+}
+
+/// Density fields of heterogeneous media. Grids are stretched over `bounds`,
+/// the box around the boundary of the medium, unless given a `min` and `max`.
+fn parse_density(
+    json: &Json,
+    bounds: Option<Aabb>,
+    base_dir: &Path,
+) -> Result<Arc<dyn Density>, JsonError> {
+    let multiplier = match json.get("multiplier") {
+        Some(multiplier) if multiplier.as_f64()? < 0.0 => {
+            return Err(multiplier.error("the multiplier must not be negative"))
+        }
+        Some(multiplier) => multiplier.as_f64()?,
+        None => 1.0,
+    };
+    let density: Arc<dyn Density> = match object_type(json)? {
+        "grid" => {
+            check_fields(
+                json,
+                &["type", "path", "resolution", "min", "max", "multiplier"],
+            )?;
+            let path = base_dir.join(required(json, "path")?.as_str()?);
+            let resolution = match json.get("resolution") {
+                Some(resolution) => {
+                    let sizes = resolution
+                        .as_array()?
+                        .iter()
+                        .map(Json::as_usize)
+                        .collect::<Result<Vec<usize>, JsonError>>()?;
+                    match sizes[..] {
+                        [x, y, z] => Some([x, y, z]),
+                        _ => return Err(resolution.error("expected three voxel counts")),
+                    }
+                }
+                None => None,
+            };
+            let (min, max) = match (json.get("min"), json.get("max"), bounds) {
+                (Some(min), Some(max), _) => (parse_vec3(min)?, parse_vec3(max)?),
+                (None, None, Some(bounds)) => (*bounds.minimum(), *bounds.maximum()),
+                _ => return Err(json.error("the grid needs both a min and a max")),
+            };
+            if (0..3).any(|axis| max[axis] <= min[axis]) {
+                return Err(json.error("the grid must extend along every axis"));
+            }
+            let grid = VoxelGrid::load(&path, resolution)
+                .map_err(|error| json.error(error.to_string()))?;
+            Arc::new(GridDensity::new(grid, &min, &max, multiplier))
+        }
+        "noise" => {
+            check_fields(json, &["type", "scale", "coverage", "multiplier"])?;
+            let coverage = match json.get("coverage") {
+                Some(coverage) => coverage.as_f64()?,
+                None => 0.5,
+            };
+            Arc::new(NoiseDensity::new(
+                texture_scale(json)?,
+                coverage,
+                multiplier,
+            ))
+        }
+        other => return Err(json.error(format!("unknown density type '{other}'"))),
+    };
+    // delta tracking can't take a single step through an infinite density
+    if !density.max_value().is_finite() {
+        return Err(json.error("the density must be finite everywhere"));
+    }
+    Ok(density)
+}
+
+/// A texture is either a plain color or an object with a `type`.
+fn parse_texture(json: &Json, base_dir: &Path) -> Result<Arc<dyn Texture>, JsonError> {
+    if json.as_array().is_ok() {
+        return Ok(Arc::new(SolidColor::new(&parse_vec3(json)?)));
+    }
+    match object_type(json)? {
+        "solid" => {
+            check_fields(json, &["type", "color"])?;
+            Ok(Arc::new(SolidColor::new(&parse_vec3(required(
+                json, "color",
+            )?)?)))
+        }
+        "checker" => {
+            check_fields(json, &["type", "even", "odd", "scale"])?;
+            Ok(Arc::new(CheckerTexture::new(
+                parse_texture(required(json, "even")?, base_dir)?,
+                parse_texture(required(json, "odd")?, base_dir)?,
+                texture_scale(json)?,
+            )))
+        }
+        "image" => {
+            check_fields(json, &["type", "path", "filter", "wrap"])?;
+            let path = base_dir.join(required(json, "path")?.as_str()?);
+            let filter = match json.get("filter") {
+                None => Filter::default(),
+                Some(filter) => match filter.as_str()? {
+                    "nearest" => Filter::Nearest,
+                    "bilinear" => Filter::Bilinear,
+                    other => return Err(filter.error(format!("unknown filter '{other}'"))),
+                },
+            };
+            let wrap = match json.get("wrap") {
+                None => Wrap::default(),
+                Some(wrap) => match wrap.as_str()? {
+                    "repeat" => Wrap::Repeat,
+                    "clamp" => Wrap::Clamp,
+                    other => return Err(wrap.error(format!("unknown wrap mode '{other}'"))),
+                },
+            };
+            let image = HdrImage::load(&path).map_err(|error| json.error(error.to_string()))?;
+            Ok(Arc::new(ImageTexture::new(image, filter, wrap)))
+        }
+        "marble" => {
+            check_fields(json, &["type", "scale", "color", "vein"])?;
+            Ok(Arc::new(MarbleTexture::new(
+                texture_scale(json)?,
+                &optional_vec3(json, "color", Vec3::new(0.9, 0.9, 0.88))?,
+                &optional_vec3(json, "vein", Vec3::new(0.15, 0.15, 0.2))?,
+            )))
+        }
+        "wood" => {
+            check_fields(json, &["type", "scale", "light", "dark"])?;
+            Ok(Arc::new(WoodTexture::new(
+                texture_scale(json)?,
+                &optional_vec3(json, "light", Vec3::new(0.72, 0.52, 0.3))?,
+                &optional_vec3(json, "dark", Vec3::new(0.4, 0.24, 0.11))?,
+            )))
+        }
+        "clouds" => {
+            check_fields(json, &["type", "scale", "coverage", "cloud", "sky"])?;
+            let coverage = match json.get("coverage") {
+                Some(coverage) => coverage.as_f64()?,
+                None => 0.5,
+            };
+            Ok(Arc::new(CloudTexture::new(
+                texture_scale(json)?,
+                coverage,
+                &optional_vec3(json, "cloud", Vec3::new(1.0, 1.0, 1.0))?,
+                &optional_vec3(json, "sky", Vec3::new(0.3, 0.5, 0.9))?,
+            )))
+        }
+        other => Err(json.error(format!("unknown texture type '{other}'"))),
+    }
+}
+
+fn texture_scale(json: &Json) -> Result<f64, JsonError> {
+    match json.get("scale") {
+        Some(scale) if scale.as_f64()? <= 0.0 => Err(scale.error("the scale must be positive")),
+        Some(scale) => scale.as_f64(),
+        None => Ok(1.0),
+    }
+}
+
+fn optional_vec3(json: &Json, key: &str, default: Vec3) -> Result<Vec3, JsonError> {
+    json.get(key).map_or(Ok(default), parse_vec3)
+}
+
+fn object_type(json: &Json) -> Result<&str, JsonError> {
+    required(json, "type")?.as_str()
+}
+
+fn required<'a>(json: &'a Json, key: &str) -> Result<&'a Json, JsonError> {
+    json.as_object()?;
+    json.get(key)
+        .ok_or_else(|| json.error(format!("missing field '{key}'")))
+}
+
+/// Rejects members that aren't in `allowed`, which are most likely typos.
+fn check_fields(json: &Json, allowed: &[&str]) -> Result<(), JsonError> {
+    for (name, value) in json.as_object()? {
+        if !allowed.contains(&name.as_str()) {
+            return Err(value.error(format!("unknown field '{name}'")));
+        }
+    }
+    Ok(())
+}
+
+fn parse_vec3(json: &Json) -> Result<Vec3, JsonError> {
+    match json.as_array()? {
+        [x, y, z] => Ok(Vec3::new(x.as_f64()?, y.as_f64()?, z.as_f64()?)),
+        _ => Err(json.error("expected an array of three numbers")),
+    }
+}
+
+/// Either a single step or a list of steps applied in order, each step being
+/// one of
+///
+/// ```json
+/// { "translate": [0, 1, 0] }
+/// { "scale": 2 }
+/// { "scale": [1, 2, 1] }
+/// { "rotate": { "axis": [0, 1, 0], "angle": 45 } }
+/// { "matrix": [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0]] }
+/// ```
+///
+/// where angles are in degrees and the rows of a matrix can leave out the
+/// last, `[0, 0, 0, 1]`. Translations, scales and angles can also be given as
+/// `{ "from": ..., "to": ... }` to move from one to the other between the
+/// times 0 and 1.
+fn parse_transform(json: &Json) -> Result<Motion, JsonError> {
+    let motion = Motion::new(0.0, 1.0);
+    match json.as_array() {
+        Ok(steps) => steps
+            .iter()
+            .try_fold(motion, |motion, step| parse_transform_step(step, motion)),
+        Err(_) => parse_transform_step(json, motion),
+    }
+}
+
+fn parse_transform_step(json: &Json, motion: Motion) -> Result<Motion, JsonError> {
+    let (operation, value) = match json.as_object()? {
+        [(operation, value)] => (operation.as_str(), value),
+        _ => return Err(json.error("expected a single transform step")),
+    };
+    match operation {
+        "translate" => {
+            let (from, to) = parse_animated(value, parse_vec3)?;
+            Ok(motion.translate(&from, &to))
+        }
+        "scale" => {
+            let (from, to) = parse_animated(value, |value| match value.as_f64() {
+                Ok(factor) => Ok(Vec3::new(factor, factor, factor)),
+                Err(_) => parse_vec3(value),
+            })?;
+            for axis in 0..3 {
+                if from[axis] == 0.0 || to[axis] == 0.0 {
+                    return Err(value.error("scale factors must not be zero"));
+                }
+                if (from[axis] < 0.0) != (to[axis] < 0.0) {
+                    return Err(value.error("scale factors must not change sign"));
+                }
+            }
+            Ok(motion.scale(&from, &to))
+        }
+        "rotate" => {
+            check_fields(value, &["axis", "angle"])?;
+            let (from, to) = parse_animated(required(value, "angle")?, Json::as_f64)?;
+            Ok(motion.rotate(&parse_direction(required(value, "axis")?)?, from, to))
+        }
+        "matrix" => {
+            let rows = value
+                .as_array()?
+                .iter()
+                .map(|row| match row.as_array()? {
+                    [a, b, c, d] => Ok([a.as_f64()?, b.as_f64()?, c.as_f64()?, d.as_f64()?]),
+                    _ => Err(row.error("expected a row of four numbers")),
+                })
+                .collect::<Result<Vec<[f64; 4]>, JsonError>>()?;
+            let matrix = match rows[..] {
+                [a, b, c] => [a, b, c, [0.0, 0.0, 0.0, 1.0]],
+                [a, b, c, d] => [a, b, c, d],
+                _ => return Err(value.error("expected three or four rows")),
+            };
+            let transform = Transform::from_matrix(matrix)
+                .ok_or_else(|| value.error("the matrix must be affine and invertible"))?;
+            Ok(motion.then(&transform))
+        }
+        other => Err(json.error(format!("unknown transform '{other}'"))),
+    }
+}
+
+/// A value that is either fixed or goes `from` one value `to` another while
+/// the shutter is open.
+fn parse_animated<T: Copy>(
+    json: &Json,
+    parse: impl Fn(&Json) -> Result<T, JsonError>,
+) -> Result<(T, T), JsonError> {
+    if json.get("from").is_none() && json.get("to").is_none() {
+        let value = parse(json)?;
+        return Ok((value, value));
+    }
+    check_fields(json, &["from", "to"])?;
+    Ok((
+        parse(required(json, "from")?)?,
+        parse(required(json, "to")?)?,
+    ))
+}
+
+/// A vector that only matters for its direction, so it can't be zero.
+fn parse_direction(json: &Json) -> Result<Vec3, JsonError> {
+    let direction = parse_vec3(json)?;
+    if direction.is_near_zero() {
+        return Err(json.error("expected a non-zero direction"));
+    }
+    Ok(direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Scene, SceneError};
+    use crate::image::{HdrImage, ImageFormat};
+    use crate::integrator::{Aov, IntegratorKind};
+    use crate::ray::Ray;
+    use crate::vec3::Vec3;
+
+    const SCENE: &str = r#"{
+  "camera": { "look_from": [0, 0, 5], "look_at": [0, 0, 0], "vfov": 40 },
+  "render": { "width": 200, "height": 100, "samples_per_pixel": 4 },
+  "materials": {
+    "red": { "type": "lambertian", "albedo": [0.8, 0.1, 0.1] }
+  },
+  "objects": [
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "red" },
+    { "type": "triangle", "vertices": [[-1, -1, -3], [1, -1, -3], [0, 1, -3]],
+      "material": { "type": "metal", "albedo": [0.9, 0.9, 0.9], "fuzz": 0.1 } }
+  ]
+}"#;
+
+    /// The message of the error that parsing `source` ends with, looking up
+    /// files in `base_dir`.
+    fn parse_error(source: &str, base_dir: &Path) -> String {
+        match Scene::parse(source, "test.json", base_dir) {
+            Err(SceneError::Parse { message, .. }) => message,
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let scene = Scene::parse(SCENE, "test.json", Path::new(".")).unwrap();
+        assert_eq!(scene.settings.image_width, 200);
+        assert_eq!(scene.settings.image_height, 100);
+        assert_eq!(scene.settings.samples_per_pixel, 4);
+        assert_eq!(scene.camera.aspect_ratio, 2.0);
+        assert_eq!(scene.camera.vertical_field_of_view_deg, 40.0);
+
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t_value() - 4.0).abs() < 1e-9);
+        assert_eq!(rec.object_id(), 1);
+
+        // the aspect ratio may be given with the size, as long as they agree
+        let sized = |aspect_ratio: &str| {
+            format!(
+                r#"{{ "camera": {{ "aspect_ratio": {aspect_ratio} }},
+                      "render": {{ "width": 400, "height": 300 }} }}"#
+            )
+        };
+        assert!(Scene::parse(&sized("1.3333"), "test.json", Path::new(".")).is_ok());
+        assert_eq!(
+            parse_error(&sized("2"), Path::new(".")),
+            "the aspect ratio doesn't match the image size of 400 x 300"
+        );
+    }
+
+    #[test]
+    fn test_lights() {
+        let source = r#"{
+  "background": [0, 0, 0],
+  "objects": [
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1,
+      "material": { "type": "diffuse_light", "emit": [4, 4, 4] } }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", Path::new(".")).unwrap();
+        let sky = scene.background.value(&Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!((sky.x, sky.y, sky.z), (0.0, 0.0, 0.0));
+
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        let (u, v) = rec.uv();
+        let emitted = rec.material().emitted(u, v, rec.point());
+        assert_eq!((emitted.x, emitted.y, emitted.z), (4.0, 4.0, 4.0));
+        assert_eq!(scene.lights.len(), 1);
+    }
+
+    #[test]
+    fn test_shapes() {
+        let source = r#"{
+  "materials": {
+    "white": { "type": "lambertian", "albedo": [0.7, 0.7, 0.7] },
+    "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] }
+  },
+  "objects": [
+    { "type": "plane", "point": [0, -1, 0], "normal": [0, 1, 0], "material": "white" },
+    { "type": "quad", "corner": [-1, 3, -1], "u": [2, 0, 0], "v": [0, 0, 2],
+      "material": "lamp" },
+    { "type": "disk", "center": [5, 3, 0], "normal": [0, -1, 0], "radius": 1,
+      "material": "lamp" },
+    { "type": "box", "min": [-0.5, -1, -0.5], "max": [0.5, 0, 0.5], "material": "white" }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", Path::new(".")).unwrap();
+        // the quad as two triangles and the disk
+        assert_eq!(scene.lights.len(), 3);
+
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let rec = scene
+            .world
+            .hit(
+                &Ray::new(&Vec3::new(0.0, 2.0, 0.0), &down),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.point().y - 0.0).abs() < 1e-9);
+        let rec = scene
+            .world
+            .hit(
+                &Ray::new(&Vec3::new(3.0, 2.0, 0.0), &down),
+                0.001,
+                f64::INFINITY,
+            )
+            .unwrap();
+        assert!((rec.point().y + 1.0).abs() < 1e-9);
+
+        let error = |object: &str| {
+            let source = format!(
+                r#"{{ "materials": {{ "white": {{ "type": "lambertian", "albedo": [1, 1, 1] }} }},
+                     "objects": [{object}] }}"#
+            );
+            parse_error(&source, Path::new("."))
+        };
+        assert_eq!(
+            error(
+                r#"{ "type": "plane", "point": [0, 0, 0], "normal": [0, 0, 0], "material": "white" }"#
+            ),
+            "expected a non-zero direction"
+        );
+        assert_eq!(
+            error(
+                r#"{ "type": "quad", "corner": [0, 0, 0], "u": [1, 0, 0], "v": [2, 0, 0], "material": "white" }"#
+            ),
+            "the edges of a quad must not be parallel"
+        );
+        assert_eq!(
+            error(
+                r#"{ "type": "disk", "center": [0, 0, 0], "normal": [0, 1, 0], "radius": 0, "material": "white" }"#
+            ),
+            "the radius must be positive"
+        );
+        assert_eq!(
+            error(r#"{ "type": "box", "min": [0, 0, 0], "max": [1, 0, 1], "material": "white" }"#),
+            "the box must extend along every axis"
+        );
+    }
+
+    #[test]
+    fn test_transforms() {
+        let dir = std::env::temp_dir().join(format!("raytracer-instances-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("tri.obj"),
+            "v -1 0 -1\nv 1 0 -1\nv 0 0 1\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let source = r#"{
+  "materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+  "objects": [
+    { "type": "obj", "path": "tri.obj", "transform": { "translate": [10, 0, 0] } },
+    { "type": "obj", "path": "tri.obj",
+      "transform": [{ "scale": 2 }, { "rotate": { "axis": [0, 0, 1], "angle": 90 } }] },
+    { "type": "obj", "path": "tri.obj", "material": "lamp", "transform": { "translate": [0, -20, 0] } },
+    { "type": "quad", "corner": [0, 0, 0], "u": [1, 0, 0], "v": [0, 0, 1], "material": "lamp",
+      "transform": { "matrix": [[1, 0, 0, 0], [0, 1, 0, 5], [0, 0, 1, 0]] } },
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "lamp",
+      "transform": [{ "scale": [1, 3, 1] }, { "translate": [0, 0, -10] }] },
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "lamp",
+      "transform": [{ "scale": 2 }, { "translate": [0, 0, 10] }] }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", &dir).unwrap();
+        let hit = |origin: Vec3, direction: Vec3| {
+            scene
+                .world
+                .hit(&Ray::new(&origin, &direction), 0.001, f64::INFINITY)
+                .map(|rec| *rec.point())
+        };
+
+        let point = hit(Vec3::new(10.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((point.y - 0.0).abs() < 1e-9);
+        // the second triangle stands upright in the plane x = 0, twice as large
+        let point = hit(Vec3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)).unwrap();
+        assert!(point.x.abs() < 1e-9);
+        let point = hit(Vec3::new(0.0, 0.0, -20.0), Vec3::new(0.0, 0.0, 1.0)).unwrap();
+        assert!((point.z + 11.0).abs() < 1e-9);
+        assert!(hit(Vec3::new(0.0, 2.5, -20.0), Vec3::new(0.0, 0.0, 1.0)).is_some());
+
+        // the same file glows where the scene gives it a lamp material, and
+        // only there
+        let emissive = |origin: Vec3| {
+            let r = Ray::new(&origin, &Vec3::new(0.0, -1.0, 0.0));
+            let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+            rec.material().is_emissive()
+        };
+        assert!(emissive(Vec3::new(0.0, -15.0, 0.0)));
+        assert!(!emissive(Vec3::new(10.0, 5.0, 0.0)));
+
+        // the lamp triangle, both halves of the quad and the sphere that is
+        // still round
+        assert_eq!(scene.lights.len(), 4);
+        let origin = Vec3::new(0.5, 0.0, 0.5);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        assert!(scene
+            .lights
+            .iter()
+            .any(|light| light.pdf(&origin, &up) > 0.0));
+        let sphere_light = scene.lights.last().unwrap();
+        let sample = sphere_light.sample(&Vec3::new(0.0, 0.0, 0.0)).unwrap();
+        let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - 0.96_f64.sqrt());
+        assert!((sample.pdf - 1.0 / solid_angle).abs() < 1e-6 * sample.pdf);
+
+        let error = |transform: &str| {
+            let source = format!(
+                r#"{{ "objects": [{{ "type": "sphere", "center": [0, 0, 0], "radius": 1,
+                     "material": {{ "type": "lambertian", "albedo": [1, 1, 1] }},
+                     "transform": {transform} }}] }}"#
+            );
+            parse_error(&source, Path::new("."))
+        };
+        assert_eq!(error(r#"{ "scale": 0 }"#), "scale factors must not be zero");
+        assert_eq!(
+            error(r#"{ "matrix": [[1, 0, 0, 0], [1, 0, 0, 0], [0, 0, 1, 0]] }"#),
+            "the matrix must be affine and invertible"
+        );
+        assert_eq!(
+            error(r#"{ "translate": [1, 0, 0], "scale": 2 }"#),
+            "expected a single transform step"
+        );
+        assert_eq!(error(r#"[{ "shear": 1 }]"#), "unknown transform 'shear'");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_motion() {
+        let source = r#"{
+  "camera": { "shutter": [0, 1] },
+  "materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+  "objects": [
+    { "type": "sphere", "center": { "from": [0, 0, 0], "to": [4, 0, 0] }, "radius": 1,
+      "material": "lamp" },
+    { "type": "quad", "corner": [0, 0, 0], "u": [1, 0, 0], "v": [0, 0, 1], "material": "lamp",
+      "transform": { "translate": { "from": [0, 5, 0], "to": [0, 6, 0] } } },
+    { "type": "quad", "corner": [0, 0, 0], "u": [1, 0, 0], "v": [0, 0, 1], "material": "lamp",
+      "transform": { "rotate": { "axis": [0, 1, 0], "angle": { "from": 90, "to": 90 } } } }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", Path::new(".")).unwrap();
+        assert_eq!(
+            (scene.camera.shutter_open, scene.camera.shutter_close),
+            (0.0, 1.0)
+        );
+        // only the quad that stays put
+        assert_eq!(scene.lights.len(), 2);
+
+        let hit = |origin: Vec3, direction: Vec3, time: f64| {
+            scene
+                .world
+                .hit(
+                    &Ray::with_time(&origin, &direction, time),
+                    0.001,
+                    f64::INFINITY,
+                )
+                .map(|rec| *rec.point())
+        };
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let point = hit(Vec3::new(4.0, 10.0, 0.0), down, 1.0).unwrap();
+        assert!((point.y - 1.0).abs() < 1e-9);
+        assert!(hit(Vec3::new(4.0, 10.0, 0.0), down, 0.0).is_none());
+        let point = hit(Vec3::new(0.5, 10.0, 0.5), down, 0.5).unwrap();
+        assert!((point.y - 5.5).abs() < 1e-9);
+
+        // motion always takes from time 0 to 1, the shutter picks the part of
+        // it that is seen
+        let late = Scene::parse(
+            r#"{ "camera": { "shutter": [0.5, 1] } }"#,
+            "test.json",
+            Path::new("."),
+        )
+        .unwrap()
+        .camera
+        .build();
+        for _ in 0..100 {
+            let time = late.get_ray(0.5, 0.5).time();
+            assert!((0.5..=1.0).contains(&time));
+        }
+        let point = hit(Vec3::new(3.0, 10.0, 0.0), down, 0.75).unwrap();
+        assert!((point.y - 1.0).abs() < 1e-9);
+
+        let error =
+            |camera: &str| parse_error(&format!(r#"{{ "camera": {camera} }}"#), Path::new("."));
+        assert_eq!(
+            error(r#"{ "shutter": [1, 0] }"#),
+            "the shutter must open before it closes"
+        );
+    }
+
+    #[test]
+    fn test_medium() {
+        let source = r#"{
+  "materials": { "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] } },
+  "objects": [
+    { "type": "medium", "density": 100,
+      "boundary": { "type": "sphere", "center": [0, 0, 0], "radius": 1 },
+      "material": { "type": "isotropic", "albedo": [0.5, 0.5, 0.5] } },
+    { "type": "medium", "density": 100, "transform": { "translate": [0, 0, -10] },
+      "boundary": { "type": "box", "min": [-1, -1, -1], "max": [1, 1, 1], "material": "lamp" },
+      "material": { "type": "isotropic", "albedo": [0.5, 0.5, 0.5] } }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", Path::new(".")).unwrap();
+        // boundaries are never seen, lamps or not
+        assert!(scene.lights.is_empty());
+
+        // dense enough that rays hardly get past the edge
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!(rec.t_value() >= 4.0 && rec.t_value() < 4.2);
+        assert_eq!(rec.material().albedo(&rec).x, 0.5);
+        let rec = scene.world.hit(&r, 6.5, f64::INFINITY).unwrap();
+        assert!(rec.t_value() >= 14.0 && rec.t_value() < 14.2);
+
+        let error = |object: &str| {
+            let source = format!(r#"{{ "objects": [{object}] }}"#);
+            parse_error(&source, Path::new("."))
+        };
+        assert_eq!(
+            error(
+                r#"{ "type": "medium", "density": 0, "material": { "type": "isotropic", "albedo": [1, 1, 1] },
+                     "boundary": { "type": "sphere", "center": [0, 0, 0], "radius": 1 } }"#
+            ),
+            "the density must be positive"
+        );
+        // only boundaries can do without a material
+        assert_eq!(
+            error(r#"{ "type": "sphere", "center": [0, 0, 0], "radius": 1 }"#),
+            "missing field 'material'"
+        );
+    }
+
+    #[test]
+    fn test_density_fields() {
+        let dir = std::env::temp_dir().join(format!("raytracer-media-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // empty at the bottom and dense at the top
+        std::fs::write(dir.join("smoke.grid"), "1 2 1\n0\n50\n").unwrap();
+        let raw: Vec<u8> = [0.0f32, 50.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        std::fs::write(dir.join("smoke.raw"), raw).unwrap();
+
+        let source = r#"{
+  "materials": { "smoke": { "type": "henyey_greenstein", "albedo": [0.5, 0.5, 0.5], "g": 0.6 } },
+  "objects": [
+    { "type": "medium", "material": "smoke", "density": { "type": "grid", "path": "smoke.grid" },
+      "boundary": { "type": "box", "min": [-1, -1, -1], "max": [1, 1, 1] } },
+    { "type": "medium", "material": "smoke",
+      "density": { "type": "grid", "path": "smoke.raw", "resolution": [1, 2, 1],
+                   "min": [9, -1, -1], "max": [11, 1, 1] },
+      "boundary": { "type": "sphere", "center": [10, 0, 0], "radius": 1 } },
+    { "type": "medium", "material": "smoke",
+      "density": { "type": "noise", "scale": 2, "coverage": 0.6, "multiplier": 10 },
+      "boundary": { "type": "sphere", "center": [20, 0, 0], "radius": 1 } }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", &dir).unwrap();
+        for x in [0.0, 10.0] {
+            // through the dense top, hardly any light gets past the edge
+            let top = Ray::new(&Vec3::new(x, 0.9, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+            let rec = scene.world.hit(&top, 0.001, f64::INFINITY).unwrap();
+            assert!(rec.point().z > 0.0);
+            assert!(scene.world.transmittance(&top, 0.001, f64::INFINITY) < 1e-3);
+            // while the bottom is clear
+            let bottom = Ray::new(&Vec3::new(x, -0.8, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+            assert!(scene.world.hit(&bottom, 0.001, f64::INFINITY).is_none());
+        }
+        let clouds = Ray::new(&Vec3::new(20.0, 5.0, 0.0), &Vec3::new(0.0, -1.0, 0.0));
+        assert!(scene.world.transmittance(&clouds, 0.001, f64::INFINITY) < 1.0);
+
+        let error = |density: &str| {
+            let source = format!(
+                r#"{{ "objects": [{{ "type": "medium", "density": {density},
+                     "material": {{ "type": "isotropic", "albedo": [1, 1, 1] }},
+                     "boundary": {{ "type": "plane", "point": [0, 0, 0], "normal": [0, 1, 0] }} }}] }}"#
+            );
+            parse_error(&source, &dir)
+        };
+        assert_eq!(
+            error(r#"{ "type": "grid", "path": "smoke.grid" }"#),
+            "the grid needs both a min and a max"
+        );
+        assert_eq!(
+            error(
+                r#"{ "type": "grid", "path": "smoke.raw", "resolution": [2, 2, 1],
+                     "min": [0, 0, 0], "max": [1, 1, 1] }"#
+            ),
+            format!(
+                "{}: expected 16 bytes for 2 x 2 x 1 voxels, found 8",
+                dir.join("smoke.raw").display()
+            )
+        );
+        assert_eq!(
+            error(
+                r#"{ "type": "grid", "path": "smoke.grid", "multiplier": 1e307,
+                     "min": [0, 0, 0], "max": [1, 1, 1] }"#
+            ),
+            "the density must be finite everywhere"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_integrator() {
+        let parse = |integrator: &str| {
+            let source = format!(r#"{{ "render": {{ "integrator": {integrator} }} }}"#);
+            Scene::parse(&source, "test.json", Path::new("."))
+                .map(|scene| scene.settings.integrator)
+        };
+        assert_eq!(parse(r#""whitted""#).unwrap(), IntegratorKind::Whitted);
+        assert_eq!(
+            parse(r#"{ "type": "ambient_occlusion", "distance": 2 }"#).unwrap(),
+            IntegratorKind::AmbientOcclusion { distance: 2.0 }
+        );
+        assert_eq!(
+            parse(r#""object_id""#).unwrap(),
+            IntegratorKind::Aov(Aov::ObjectId)
+        );
+        assert!(parse(r#""photon_mapping""#).is_err());
+        assert!(parse(r#"{ "type": "path", "distance": 2 }"#).is_err());
+
+        assert_eq!(
+            parse_error(
+                r#"{ "render": { "light_sampling": true } }"#,
+                Path::new(".")
+            ),
+            "unknown field 'light_sampling'"
+        );
+    }
+
+    #[test]
+    fn test_textures() {
+        let source = r#"{
+  "objects": [
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1,
+      "material": { "type": "lambertian", "albedo": { "type": "checker", "scale": 10,
+        "even": { "type": "solid", "color": [1, 0, 0] }, "odd": [0, 0, 1] } } }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", Path::new(".")).unwrap();
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        // the hit is at (0, 0, 1), in the even cube at the origin
+        let (attenuation, _) = rec.material().scatter(&r, rec).unwrap();
+        assert_eq!(
+            (attenuation.x, attenuation.y, attenuation.z),
+            (1.0, 0.0, 0.0)
+        );
+
+        let broken = source.replace("\"scale\": 10", "\"scale\": 0");
+        assert!(Scene::parse(&broken, "test.json", Path::new(".")).is_err());
+
+        for procedural in [
+            r#"{ "type": "marble", "scale": 4 }"#,
+            r#"{ "type": "wood", "light": [1, 1, 1] }"#,
+            r#"{ "type": "clouds", "coverage": 0.3 }"#,
+        ] {
+            let source = format!(
+                r#"{{ "materials": {{ "m": {{ "type": "metal", "albedo": {procedural} }} }} }}"#
+            );
+            assert!(Scene::parse(&source, "test.json", Path::new(".")).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_image_texture() {
+        let dir = std::env::temp_dir().join(format!("raytracer-scene-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("red.ppm"), "P3 1 1 255 255 0 0").unwrap();
+
+        let source = r#"{
+  "objects": [
+    { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": { "type": "lambertian",
+      "albedo": { "type": "image", "path": "red.ppm", "filter": "nearest", "wrap": "clamp" } } }
+  ]
+}"#;
+        let scene = Scene::parse(source, "test.json", &dir).unwrap();
+        let r = Ray::new(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -1.0));
+        let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+        let (attenuation, _) = rec.material().scatter(&r, rec).unwrap();
+        assert_eq!((attenuation.x, attenuation.y), (1.0, 0.0));
+
+        let missing = source.replace("red.ppm", "blue.ppm");
+        match Scene::parse(&missing, "test.json", &dir) {
+            Err(SceneError::Parse { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert!(message.contains("blue.ppm"));
+            }
+            _ => panic!("expected a parse error"),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_background() {
+        let dir = std::env::temp_dir().join(format!("raytracer-env-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sky = HdrImage::new(1, 1, vec![Vec3::new(2.0, 1.0, 0.5)]);
+        std::fs::write(dir.join("sky.hdr"), sky.encode(ImageFormat::Hdr)).unwrap();
+
+        let source =
+            r#"{ "background": { "type": "environment", "path": "sky.hdr", "intensity": 2 } }"#;
+        let scene = Scene::parse(source, "test.json", &dir).unwrap();
+        let radiance = scene.background.value(&Vec3::new(0.0, 0.0, 1.0));
+        // RGBE rounds to within 1/256 of the brightest channel
+        assert!((radiance.x - 4.0).abs() < 0.04);
+        assert!((radiance.z - 1.0).abs() < 0.04);
+
+        let source = r#"{ "background": { "type": "gradient", "top": [0, 0, 1] } }"#;
+        let scene = Scene::parse(source, "test.json", &dir).unwrap();
+        assert_eq!(scene.background.value(&Vec3::new(0.0, 1.0, 0.0)).x, 0.0);
+        assert_eq!(scene.background.value(&Vec3::new(0.0, -1.0, 0.0)).x, 1.0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_location() {
+        let source = SCENE.replace("\"material\": \"red\"", "\"material\": \"blue\"");
+        match Scene::parse(&source, "test.json", Path::new(".")) {
+            Err(SceneError::Parse {
+                line,
+                column,
+                message,
+                ..
+            }) => {
+                assert_eq!((line, column), (8, 71));
+                assert_eq!(message, "unknown material 'blue'");
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
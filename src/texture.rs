@@ -0,0 +1,240 @@
+use crate::{
+    image::{Filter, HdrImage, Wrap},
+    perlin::Perlin,
+    vec3::Vec3,
+};
+use std::sync::Arc;
+
+/// A color that varies over a surface, looked up with the surface coordinates
+/// `u` and `v` of a hit as well as the point itself.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: &Vec3) -> Vec3;
+}
+
+pub struct SolidColor {
+    color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(color: &Vec3) -> SolidColor {
+        SolidColor { color: *color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// Alternates between two textures in cubes of size `scale`. The pattern lives
+/// in space rather than on the surface, so it doesn't stretch around curves.
+pub struct CheckerTexture {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl CheckerTexture {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> CheckerTexture {
+        CheckerTexture { even, odd, scale }
+    }
+
+    pub fn from_colors(even: &Vec3, odd: &Vec3, scale: f64) -> CheckerTexture {
+        CheckerTexture::new(
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+            scale,
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, point: &Vec3) -> Vec3 {
+        let cell = |c: f64| (c / self.scale).floor() as i64;
+        if (cell(point.x) + cell(point.y) + cell(point.z)).rem_euclid(2) == 0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+/// Maps an image onto the surface coordinates, with `(0, 0)` at the bottom
+/// left corner of the image and `(1, 1)` at the top right.
+pub struct ImageTexture {
+    image: HdrImage,
+    filter: Filter,
+    wrap: Wrap,
+}
+
+impl ImageTexture {
+    /// `image` holds linear colors, see `HdrImage::from_image` for images
+    /// that are gamma encoded.
+    pub fn new(image: HdrImage, filter: Filter, wrap: Wrap) -> ImageTexture {
+        ImageTexture {
+            image,
+            filter,
+            wrap,
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Vec3) -> Vec3 {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            // a cyan that stands out, to make missing texture data obvious
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+        self.image.sample(u, v, self.filter, self.wrap)
+    }
+}
+
+/// Veined stone: bands along z at `scale` per unit, distorted by turbulence
+/// and blended from `vein` to `color`.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    color: Vec3,
+    vein: Vec3,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, color: &Vec3, vein: &Vec3) -> MarbleTexture {
+        MarbleTexture {
+            noise: Perlin::new(),
+            scale,
+            color: *color,
+            vein: *vein,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Vec3) -> Vec3 {
+        let p = *point * self.scale;
+        let t = 0.5 * (1.0 + (p.z + 10.0 * self.noise.turbulence(&p, 7)).sin());
+        lerp(&self.vein, &self.color, t)
+    }
+}
+
+/// Growth rings around the y axis, `scale` rings per unit, wobbled by noise.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    light: Vec3,
+    dark: Vec3,
+}
+
+impl WoodTexture {
+    pub fn new(scale: f64, light: &Vec3, dark: &Vec3) -> WoodTexture {
+        WoodTexture {
+            noise: Perlin::new(),
+            scale,
+            light: *light,
+            dark: *dark,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Vec3) -> Vec3 {
+        let p = *point * self.scale;
+        let radius = (p.x * p.x + p.z * p.z).sqrt() + 0.5 * self.noise.fbm(&(p * 0.5), 4, 2.0, 0.5);
+        let ring = radius.rem_euclid(1.0);
+        // a sharp transition from early to late wood in every ring
+        lerp(&self.light, &self.dark, ring.powi(3))
+    }
+}
+
+/// Clouds from fBm noise at `scale` per unit. `coverage` in `[0, 1]` is about
+/// the fraction of `sky` covered with `cloud`.
+pub struct CloudTexture {
+    noise: Perlin,
+    scale: f64,
+    coverage: f64,
+    cloud: Vec3,
+    sky: Vec3,
+}
+
+impl CloudTexture {
+    pub fn new(scale: f64, coverage: f64, cloud: &Vec3, sky: &Vec3) -> CloudTexture {
+        CloudTexture {
+            noise: Perlin::new(),
+            scale,
+            coverage,
+            cloud: *cloud,
+            sky: *sky,
+        }
+    }
+}
+
+impl Texture for CloudTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Vec3) -> Vec3 {
+        let density = self.noise.fbm(&(*point * self.scale), 6, 2.0, 0.5);
+        // fbm rarely leaves [-0.5, 0.5], shift it by the coverage and fade in
+        let t = ((density + self.coverage - 0.5) * 4.0).clamp(0.0, 1.0);
+        lerp(&self.sky, &self.cloud, t)
+    }
+}
+
+fn lerp(a: &Vec3, b: &Vec3, t: f64) -> Vec3 {
+    *a * (1.0 - t) + *b * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckerTexture, CloudTexture, ImageTexture, MarbleTexture, Texture, WoodTexture};
+    use crate::{
+        bad_rand,
+        image::{Filter, HdrImage, Wrap},
+        vec3::Vec3,
+    };
+
+    #[test]
+    fn test_checker() {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let checker = CheckerTexture::from_colors(&black, &white, 0.5);
+        assert_eq!(checker.value(0.0, 0.0, &Vec3::new(0.1, 0.1, 0.1)).x, 0.0);
+        assert_eq!(checker.value(0.0, 0.0, &Vec3::new(0.6, 0.1, 0.1)).x, 1.0);
+        assert_eq!(checker.value(0.0, 0.0, &Vec3::new(-0.1, 0.1, 0.1)).x, 1.0);
+        assert_eq!(checker.value(0.0, 0.0, &Vec3::new(0.6, 0.6, 0.1)).x, 0.0);
+    }
+
+    #[test]
+    fn test_procedural() {
+        bad_rand::seed(3);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(MarbleTexture::new(4.0, &white, &black)),
+            Box::new(WoodTexture::new(4.0, &white, &black)),
+            Box::new(CloudTexture::new(2.0, 0.5, &white, &black)),
+        ];
+        for texture in textures {
+            let values: Vec<f64> = (0..200)
+                .map(|i| {
+                    texture
+                        .value(0.0, 0.0, &Vec3::new(i as f64 * 0.05, 0.3, i as f64 * 0.031))
+                        .x
+                })
+                .collect();
+            assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+            // the pattern actually varies
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            assert!(values.iter().any(|v| (v - mean).abs() > 0.1));
+        }
+    }
+
+    #[test]
+    fn test_image() {
+        let pixels = (0..6).map(|i| Vec3::new(i as f64, 0.0, 0.0)).collect();
+        let texture = ImageTexture::new(HdrImage::new(2, 3, pixels), Filter::Nearest, Wrap::Clamp);
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        // v points up, so the bottom left corner is the first pixel of the last row
+        assert_eq!(texture.value(0.0, 0.0, &origin).x, 3.0);
+        assert_eq!(texture.value(1.0, 1.0, &origin).x, 2.0);
+        assert_eq!(texture.value(0.5, 0.9, &origin).x, 1.0);
+    }
+}
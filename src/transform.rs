@@ -0,0 +1,447 @@
+use crate::{hittable::aabb::Aabb, vec3::Vec3};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// An affine transform, kept together with its inverse so that rays can be
+/// taken into object space as cheaply as points are taken out of it.
+#[derive(Clone, Copy)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    pub fn translation(offset: &Vec3) -> Transform {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Scales by a factor per axis, none of which may be zero.
+    pub fn scaling(factors: &Vec3) -> Transform {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][axis] = factors[axis];
+            inverse[axis][axis] = 1.0 / factors[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Rotates counter-clockwise by `degrees` when looking down `axis`
+    /// towards the origin.
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Transform {
+        let a = axis.normal();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let matrix = [
+            [
+                cos + a.x * a.x * (1.0 - cos),
+                a.x * a.y * (1.0 - cos) - a.z * sin,
+                a.x * a.z * (1.0 - cos) + a.y * sin,
+                0.0,
+            ],
+            [
+                a.y * a.x * (1.0 - cos) + a.z * sin,
+                cos + a.y * a.y * (1.0 - cos),
+                a.y * a.z * (1.0 - cos) - a.x * sin,
+                0.0,
+            ],
+            [
+                a.z * a.x * (1.0 - cos) - a.y * sin,
+                a.z * a.y * (1.0 - cos) + a.x * sin,
+                cos + a.z * a.z * (1.0 - cos),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        // rotations are orthogonal
+        Transform {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    /// Any affine matrix, given as rows. `None` if its last row isn't
+    /// `[0, 0, 0, 1]` or it can't be inverted.
+    pub fn from_matrix(matrix: Matrix) -> Option<Transform> {
+        if matrix[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        Some(Transform {
+            matrix,
+            inverse: invert(&matrix)?,
+        })
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, point: &Vec3) -> Vec3 {
+        apply(&self.matrix, point, 1.0)
+    }
+
+    pub fn vector(&self, vector: &Vec3) -> Vec3 {
+        apply(&self.matrix, vector, 0.0)
+    }
+
+    /// Surface normals are transformed by the inverse transpose, which keeps
+    /// them perpendicular to the transformed surface. The result is not
+    /// normalized.
+    pub fn normal(&self, normal: &Vec3) -> Vec3 {
+        apply(&transpose(&self.inverse), normal, 0.0)
+    }
+
+    /// Box enclosing the transformed corners of `bounding_box`.
+    pub fn bounding_box(&self, bounding_box: &Aabb) -> Aabb {
+        let corners = box_corners(bounding_box).map(|corner| self.point(&corner));
+        corners[1..]
+            .iter()
+            .fold(Aabb::new(&corners[0], &corners[0]), |acc, corner| {
+                acc.including(corner)
+            })
+    }
+
+    /// The factor by which the transform scales every length, if it doesn't
+    /// stretch or shear, i.e. if it keeps angles and shapes as they are.
+    pub fn uniform_scale(&self) -> Option<f64> {
+        const TOLERANCE: f64 = 1e-9;
+
+        let columns = [0, 1, 2].map(|column| {
+            Vec3::new(
+                self.matrix[0][column],
+                self.matrix[1][column],
+                self.matrix[2][column],
+            )
+        });
+        let scale_squared = columns[0].length_squared();
+        let tolerance = TOLERANCE * scale_squared;
+        let uniform = columns
+            .iter()
+            .all(|column| (column.length_squared() - scale_squared).abs() <= tolerance)
+            && columns[0].dot(&columns[1]).abs() <= tolerance
+            && columns[1].dot(&columns[2]).abs() <= tolerance
+            && columns[2].dot(&columns[0]).abs() <= tolerance;
+        uniform.then(|| scale_squared.sqrt())
+    }
+}
+
+/// A transform that changes while the shutter is open. It is built from steps
+/// like a `Transform` is, but the parameters of each step can go from one
+/// value at `time0` to another at `time1`, so that a rotation can spin all
+/// the way around. Before and after that the steps keep their first and last
+/// values.
+#[derive(Clone)]
+pub struct Motion {
+    steps: Vec<MotionStep>,
+    time0: f64,
+    time1: f64,
+}
+
+#[derive(Clone)]
+enum MotionStep {
+    Fixed(Box<Transform>),
+    Translate(Vec3, Vec3),
+    Scale(Vec3, Vec3),
+    Rotate(Vec3, f64, f64),
+}
+
+impl Motion {
+    /// Starts out as the identity.
+    pub fn new(time0: f64, time1: f64) -> Motion {
+        Motion {
+            steps: Vec::new(),
+            time0,
+            time1,
+        }
+    }
+
+    /// Continues with a transform that stays the same.
+    pub fn then(mut self, transform: &Transform) -> Motion {
+        self.steps.push(MotionStep::Fixed(Box::new(*transform)));
+        self
+    }
+
+    pub fn translate(mut self, from: &Vec3, to: &Vec3) -> Motion {
+        self.steps.push(MotionStep::Translate(*from, *to));
+        self
+    }
+
+    /// Factors on the way from `from` to `to` must not pass through zero.
+    pub fn scale(mut self, from: &Vec3, to: &Vec3) -> Motion {
+        self.steps.push(MotionStep::Scale(*from, *to));
+        self
+    }
+
+    pub fn rotate(mut self, axis: &Vec3, from_degrees: f64, to_degrees: f64) -> Motion {
+        self.steps
+            .push(MotionStep::Rotate(*axis, from_degrees, to_degrees));
+        self
+    }
+
+    /// The transform at `time`.
+    pub fn at(&self, time: f64) -> Transform {
+        let progress = if self.time1 > self.time0 {
+            ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let lerp = |from: f64, to: f64| from + (to - from) * progress;
+        let lerp_vec3 = |from: &Vec3, to: &Vec3| *from + (*to - *from) * progress;
+        self.steps
+            .iter()
+            .fold(Transform::identity(), |transform, step| {
+                transform.then(&match step {
+                    MotionStep::Fixed(fixed) => **fixed,
+                    MotionStep::Translate(from, to) => Transform::translation(&lerp_vec3(from, to)),
+                    MotionStep::Scale(from, to) => Transform::scaling(&lerp_vec3(from, to)),
+                    MotionStep::Rotate(axis, from, to) => {
+                        Transform::rotation(axis, lerp(*from, *to))
+                    }
+                })
+            })
+    }
+
+    /// Whether the transform is the same at all times.
+    pub fn is_fixed(&self) -> bool {
+        self.steps.iter().all(|step| match step {
+            MotionStep::Fixed(_) => true,
+            MotionStep::Translate(from, to) | MotionStep::Scale(from, to) => {
+                (*to - *from).length_squared() == 0.0
+            }
+            MotionStep::Rotate(_, from, to) => from == to,
+        })
+    }
+
+    /// Box enclosing `bounding_box` over the whole motion. The box is followed
+    /// in small steps and padded by how far its corners move in one of them,
+    /// which covers the arcs rotations take in between.
+    pub fn bounding_box(&self, bounding_box: &Aabb) -> Aabb {
+        const MOTION_SAMPLES: usize = 64;
+
+        let corners = box_corners(bounding_box);
+        let at = |i: usize| {
+            let time =
+                self.time0 + (self.time1 - self.time0) * i as f64 / (MOTION_SAMPLES - 1) as f64;
+            let transform = self.at(time);
+            corners.map(|corner| transform.point(&corner))
+        };
+        let mut previous = at(0);
+        let mut surrounding = Aabb::new(&previous[0], &previous[0]);
+        let mut largest_move: f64 = 0.0;
+        for i in 0..MOTION_SAMPLES {
+            let current = at(i);
+            for (corner, previous_corner) in current.iter().zip(&previous) {
+                surrounding = surrounding.including(corner);
+                largest_move = largest_move.max((*corner - *previous_corner).length());
+            }
+            previous = current;
+        }
+        let pad = Vec3::new(largest_move, largest_move, largest_move) / 2.0;
+        Aabb::new(
+            &(*surrounding.minimum() - pad),
+            &(*surrounding.maximum() + pad),
+        )
+    }
+}
+
+fn box_corners(bounding_box: &Aabb) -> [Vec3; 8] {
+    let (min, max) = (bounding_box.minimum(), bounding_box.maximum());
+    [0, 1, 2, 3, 4, 5, 6, 7].map(|i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        )
+    })
+}
+
+fn apply(matrix: &Matrix, v: &Vec3, w: f64) -> Vec3 {
+    let row =
+        |i: usize| matrix[i][0] * v.x + matrix[i][1] * v.y + matrix[i][2] * v.z + matrix[i][3] * w;
+    Vec3::new(row(0), row(1), row(2))
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 4]; 4];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    product
+}
+
+fn transpose(matrix: &Matrix) -> Matrix {
+    let mut transposed = [[0.0; 4]; 4];
+    for (i, row) in transposed.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = matrix[j][i];
+        }
+    }
+    transposed
+}
+
+/// Gauss-Jordan elimination with partial pivoting, `None` for singular
+/// matrices.
+fn invert(matrix: &Matrix) -> Option<Matrix> {
+    const EPSILON: f64 = 1e-12;
+
+    let mut m = *matrix;
+    let mut inverse = IDENTITY;
+    for column in 0..4 {
+        let pivot =
+            (column..4).max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))?;
+        if m[pivot][column].abs() < EPSILON {
+            return None;
+        }
+        m.swap(column, pivot);
+        inverse.swap(column, pivot);
+
+        let scale = 1.0 / m[column][column];
+        for j in 0..4 {
+            m[column][j] *= scale;
+            inverse[column][j] *= scale;
+        }
+        for row in 0..4 {
+            if row == column {
+                continue;
+            }
+            let factor = m[row][column];
+            for j in 0..4 {
+                m[row][j] -= factor * m[column][j];
+                inverse[row][j] -= factor * inverse[column][j];
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Motion, Transform};
+    use crate::hittable::aabb::Aabb;
+    use crate::vec3::Vec3;
+
+    fn assert_near(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).length() < 1e-9, "({}, {}, {})", a.x, a.y, a.z);
+    }
+
+    #[test]
+    fn test_transform() {
+        let transform = Transform::scaling(&Vec3::new(2.0, 1.0, 1.0))
+            .then(&Transform::rotation(&Vec3::new(0.0, 0.0, 1.0), 90.0))
+            .then(&Transform::translation(&Vec3::new(0.0, 0.0, 3.0)));
+
+        assert_near(
+            &transform.point(&Vec3::new(1.0, 0.0, 0.0)),
+            &Vec3::new(0.0, 2.0, 3.0),
+        );
+        assert_near(
+            &transform.vector(&Vec3::new(1.0, 0.0, 0.0)),
+            &Vec3::new(0.0, 2.0, 0.0),
+        );
+        let point = Vec3::new(0.3, -1.2, 4.0);
+        assert_near(&transform.inverse().point(&transform.point(&point)), &point);
+        assert!(transform.uniform_scale().is_none());
+
+        // the normal of the plane x + y = 0 stays perpendicular to it
+        let normal = transform.normal(&Vec3::new(1.0, 1.0, 0.0));
+        let in_plane = transform.vector(&Vec3::new(1.0, -1.0, 0.0));
+        assert!(normal.dot(&in_plane).abs() < 1e-9);
+
+        let similar = Transform::rotation(&Vec3::new(1.0, 1.0, 0.0), 30.0)
+            .then(&Transform::scaling(&Vec3::new(3.0, 3.0, 3.0)));
+        assert!((similar.uniform_scale().unwrap() - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_matrix() {
+        let transform = Transform::from_matrix([
+            [0.0, 0.0, 2.0, 1.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 4.0, 0.0, -1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+        .unwrap();
+        let point = Vec3::new(1.0, 2.0, 3.0);
+        assert_near(&transform.point(&point), &Vec3::new(7.0, 1.0, 7.0));
+        assert_near(&transform.inverse().point(&transform.point(&point)), &point);
+
+        let singular = [
+            [1.0, 0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        assert!(Transform::from_matrix(singular).is_none());
+        let projective = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0, 1.0],
+        ];
+        assert!(Transform::from_matrix(projective).is_none());
+    }
+
+    #[test]
+    fn test_motion() {
+        let motion = Motion::new(1.0, 3.0)
+            .then(&Transform::translation(&Vec3::new(1.0, 0.0, 0.0)))
+            .rotate(&Vec3::new(0.0, 1.0, 0.0), 0.0, 360.0)
+            .translate(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(0.0, 2.0, 0.0));
+        assert!(!motion.is_fixed());
+
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        assert_near(&motion.at(0.0).point(&origin), &Vec3::new(1.0, 0.0, 0.0));
+        // half way around and half way up
+        assert_near(&motion.at(2.0).point(&origin), &Vec3::new(-1.0, 1.0, 0.0));
+        assert_near(&motion.at(5.0).point(&origin), &Vec3::new(1.0, 2.0, 0.0));
+
+        // the point circles the y axis at a distance of 1 while rising to 2
+        let bounding_box = motion.bounding_box(&Aabb::new(&origin, &origin));
+        assert!(bounding_box.minimum().x <= -1.0 && bounding_box.maximum().x >= 1.0);
+        assert!(bounding_box.minimum().z <= -1.0 && bounding_box.maximum().z >= 1.0);
+        assert!(bounding_box.maximum().y >= 2.0 && bounding_box.maximum().y < 2.2);
+
+        let still = Motion::new(0.0, 1.0)
+            .scale(&Vec3::new(2.0, 2.0, 2.0), &Vec3::new(2.0, 2.0, 2.0))
+            .rotate(&Vec3::new(1.0, 0.0, 0.0), 30.0, 30.0);
+        assert!(still.is_fixed());
+    }
+}
@@ -25,6 +25,20 @@ impl Vec3 {
         self.x * v.x + self.y * v.y + self.z * v.z
     }
 
+    /// Right handed cross product. Note that `Mul<Vec3>` negates the y
+    /// component of the result; the camera basis relies on that.
+    pub fn cross(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * v.z - self.z * v.y,
+            self.z * v.x - self.x * v.z,
+            self.x * v.y - self.y * v.x,
+        )
+    }
+
+    pub fn component_mul(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(self.x * v.x, self.y * v.y, self.z * v.z)
+    }
+
     pub fn sqrt(&self) -> Vec3 {
         Vec3::new(self.x.sqrt(), self.y.sqrt(), self.z.sqrt())
     }
@@ -47,6 +61,14 @@ impl Vec3 {
         }
     }
 
+    /// Uniformly distributed over the surface of the unit sphere.
+    pub fn random_unit_vector() -> Vec3 {
+        let z = rand_f64() * 2.0 - 1.0;
+        let phi = rand_f64() * 2.0 * std::f64::consts::PI;
+        let r = (1.0 - z * z).sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
     pub fn get_random_point_in_unit_disk() -> Vec3 {
         loop {
             let v = Vec3::new(
@@ -61,9 +83,36 @@ impl Vec3 {
         }
     }
 
+    /// Two unit vectors that are perpendicular to this unit vector and to
+    /// each other.
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let a = if self.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = self.cross(&a).normal();
+        let u = self.cross(&v);
+        (u, v)
+    }
+
     pub fn is_near_zero(&self) -> bool {
         const NEAR_ZERO_THRESHOLD: f64 = 1e-8;
-        self.x < NEAR_ZERO_THRESHOLD && self.y < NEAR_ZERO_THRESHOLD && self.z < NEAR_ZERO_THRESHOLD
+        self.x.abs() < NEAR_ZERO_THRESHOLD
+            && self.y.abs() < NEAR_ZERO_THRESHOLD
+            && self.z.abs() < NEAR_ZERO_THRESHOLD
+    }
+}
+
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis out of range: {axis}"),
+        }
     }
 }
 
@@ -114,6 +163,7 @@ impl ops::Div<f64> for Vec3 {
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::Vec3;
 
@@ -167,6 +217,19 @@ mod tests {
         assert_eq!(result.z, -3.13);
     }
 
+    #[test]
+    fn test_right_handed_cross_product() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let result = x.cross(&y);
+        assert_eq!(result.x, 0.0);
+        assert_eq!(result.y, 0.0);
+        assert_eq!(result.z, 1.0);
+
+        let result = Vec3::new(0.0, 0.0, 1.0).cross(&x);
+        assert_eq!(result.y, 1.0);
+    }
+
     #[test]
     fn test_dot_product() {
         let v1 = Vec3::new(0.0, 3.13, 1.0);
@@ -175,6 +238,16 @@ mod tests {
         assert_eq!(result, -3.13 + 2.7123);
     }
 
+    #[test]
+    fn test_component_multiplication() {
+        let v1 = Vec3::new(0.5, 3.13, 2.0);
+        let v2 = Vec3::new(4.0, 0.0, -1.5);
+        let result = v1.component_mul(&v2);
+        assert_eq!(result.x, 2.0);
+        assert_eq!(result.y, 0.0);
+        assert_eq!(result.z, -3.0);
+    }
+
     #[test]
     fn test_normal() {
         let v = Vec3::new(0.0, 3.13, 1.0);
@@ -201,4 +274,20 @@ mod tests {
         assert_eq!(result.y, 7.512);
         assert_eq!(result.z, 2.4);
     }
+
+    #[test]
+    fn test_near_zero() {
+        assert!(Vec3::new(1e-9, -1e-9, 0.0).is_near_zero());
+        assert!(Vec3::new(-1e-9, -1e-9, -1e-9).is_near_zero());
+        // only the size of a component counts, not its sign
+        assert!(!Vec3::new(-1.0, -2.0, -3.0).is_near_zero());
+        assert!(!Vec3::new(0.0, -1.0, 0.0).is_near_zero());
+    }
+
+    #[test]
+    fn test_random_unit_vector() {
+        for _ in 0..100 {
+            assert!((Vec3::random_unit_vector().length() - 1.0).abs() < 1e-12);
+        }
+    }
 }